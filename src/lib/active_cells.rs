@@ -0,0 +1,62 @@
+const ABSENT: usize = !0;
+
+/// Indices of the cells with energy, in no particular order, with constant
+/// time insertion, removal and random access.
+#[derive(Clone)]
+pub(crate) struct ActiveCells {
+    cells: Vec<usize>,
+    slots: Vec<usize>,
+}
+
+impl ActiveCells {
+    pub(crate) fn new<I: Iterator<Item=bool>>(active: I) -> ActiveCells {
+        let mut index = ActiveCells {
+            cells: Vec::new(),
+            slots: Vec::new(),
+        };
+        for (cell, active) in active.enumerate() {
+            index.slots.push(ABSENT);
+            if active {
+                index.insert(cell);
+            }
+        }
+        index
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, slot: usize) -> usize {
+        self.cells[slot]
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, cell: usize, was_active: bool, is_active: bool) {
+        if !was_active && is_active {
+            self.insert(cell);
+        } else if was_active && !is_active {
+            self.remove(cell);
+        }
+    }
+
+    #[inline]
+    fn insert(&mut self, cell: usize) {
+        debug_assert_eq!(self.slots[cell], ABSENT);
+        self.slots[cell] = self.cells.len();
+        self.cells.push(cell);
+    }
+
+    #[inline]
+    fn remove(&mut self, cell: usize) {
+        let slot = self.slots[cell];
+        debug_assert_ne!(slot, ABSENT);
+        self.cells.swap_remove(slot);
+        if slot < self.cells.len() {
+            self.slots[self.cells[slot]] = slot;
+        }
+        self.slots[cell] = ABSENT;
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use cell::Cell;
+use genome_store::GenomeBuffer;
+
+/// The part of a cell that counts towards the pond totals.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct Contribution {
+    energy: usize,
+    generation: usize,
+}
+
+impl Contribution {
+    #[inline]
+    pub(crate) fn is_active(&self) -> bool {
+        self.energy > 0
+    }
+}
+
+impl<'a, G: GenomeBuffer> From<&'a Cell<G>> for Contribution {
+    #[inline]
+    fn from(cell: &'a Cell<G>) -> Contribution {
+        Contribution {
+            energy: cell.energy,
+            generation: cell.generation,
+        }
+    }
+}
+
+/// Totals over the cells with energy, kept up to date from the cells that
+/// change instead of scanning the pond. The counters are signed so that
+/// the changes made by a parallel tile can be gathered on their own and
+/// merged afterwards.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Aggregates {
+    energy: isize,
+    active_cells: isize,
+    viable_replicators: isize,
+    generations: BTreeMap<usize, isize>,
+}
+
+impl Aggregates {
+    pub(crate) fn scan<'a, G: GenomeBuffer, I: Iterator<Item=&'a Cell<G>>>(cells: I) -> Aggregates {
+        let mut aggregates = Aggregates::default();
+        for cell in cells {
+            aggregates.add(Contribution::from(cell), 1);
+        }
+        aggregates
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, before: Contribution, after: Contribution) {
+        if before.energy > 0 && after.energy > 0 && before.generation == after.generation {
+            // Most executions only burn energy.
+            self.energy += after.energy as isize - before.energy as isize;
+        } else if before != after {
+            self.add(before, -1);
+            self.add(after, 1);
+        }
+    }
+
+    /// Adds `other` and clears it.
+    pub(crate) fn merge(&mut self, other: &mut Aggregates) {
+        self.energy += other.energy;
+        self.active_cells += other.active_cells;
+        self.viable_replicators += other.viable_replicators;
+        for (generation, count) in other.generations.iter() {
+            self.count_generation(*generation, *count);
+        }
+        *other = Aggregates::default();
+    }
+
+    #[inline]
+    fn add(&mut self, contribution: Contribution, sign: isize) {
+        if contribution.energy == 0 {
+            return;
+        }
+        self.energy += sign * contribution.energy as isize;
+        self.active_cells += sign;
+        if contribution.generation > 2 {
+            self.viable_replicators += sign;
+        }
+        self.count_generation(contribution.generation, sign);
+    }
+
+    #[inline]
+    fn count_generation(&mut self, generation: usize, count: isize) {
+        let total = {
+            let total = self.generations.entry(generation).or_insert(0);
+            *total += count;
+            *total
+        };
+        if total == 0 {
+            self.generations.remove(&generation);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn total_energy(&self) -> usize {
+        self.energy as usize
+    }
+
+    #[inline]
+    pub(crate) fn active_cells(&self) -> usize {
+        self.active_cells as usize
+    }
+
+    #[inline]
+    pub(crate) fn viable_replicators(&self) -> usize {
+        self.viable_replicators as usize
+    }
+
+    #[inline]
+    pub(crate) fn max_generation(&self) -> usize {
+        self.generations.keys().next_back().cloned().unwrap_or(0)
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use genome::Genome;
+use instruction::Instruction;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edit {
+    Match(Instruction),
+    Substitution(Instruction, Instruction),
+    Insertion(Instruction),
+    Deletion(Instruction),
+}
+
+pub struct Alignment {
+    pub edits: Vec<Edit>,
+    pub edit_distance: usize,
+    pub hamming_distance: usize,
+}
+
+impl Alignment {
+    /// Levenshtein alignment over the instruction sequences of both genomes,
+    /// with unit cost for substitutions, insertions and deletions. Insertions
+    /// are instructions present in `to` only.
+    pub fn new(from: &Genome, to: &Genome) -> Alignment {
+        let a: Vec<Instruction> = from.instructions().collect();
+        let b: Vec<Instruction> = to.instructions().collect();
+        let columns = b.len() + 1;
+        let mut costs = vec![0usize; (a.len() + 1) * columns];
+        for (i, cost) in costs.iter_mut().step_by(columns).enumerate() {
+            *cost = i;
+        }
+        for (j, cost) in costs.iter_mut().take(columns).enumerate() {
+            *cost = j;
+        }
+        for i in 1..a.len() + 1 {
+            for j in 1..columns {
+                let substitution = costs[(i - 1) * columns + j - 1] +
+                    if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                let deletion = costs[(i - 1) * columns + j] + 1;
+                let insertion = costs[i * columns + j - 1] + 1;
+                costs[i * columns + j] = substitution.min(deletion).min(insertion);
+            }
+        }
+
+        let mut edits = Vec::with_capacity(a.len().max(b.len()));
+        let (mut i, mut j) = (a.len(), b.len());
+        while i > 0 || j > 0 {
+            let cost = costs[i * columns + j];
+            if i > 0 && j > 0 && cost == costs[(i - 1) * columns + j - 1] +
+                if a[i - 1] == b[j - 1] { 0 } else { 1 } {
+                edits.push(if a[i - 1] == b[j - 1] {
+                    Edit::Match(a[i - 1])
+                } else {
+                    Edit::Substitution(a[i - 1], b[j - 1])
+                });
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && cost == costs[(i - 1) * columns + j] + 1 {
+                edits.push(Edit::Deletion(a[i - 1]));
+                i -= 1;
+            } else {
+                edits.push(Edit::Insertion(b[j - 1]));
+                j -= 1;
+            }
+        }
+        edits.reverse();
+
+        Alignment {
+            edits,
+            edit_distance: costs[a.len() * columns + b.len()],
+            hamming_distance: from.hamming_distance(to),
+        }
+    }
+
+    #[inline]
+    pub fn substitutions(&self) -> usize {
+        self.count(|edit| matches!(edit, Edit::Substitution(_, _)))
+    }
+
+    #[inline]
+    pub fn insertions(&self) -> usize {
+        self.count(|edit| matches!(edit, Edit::Insertion(_)))
+    }
+
+    #[inline]
+    pub fn deletions(&self) -> usize {
+        self.count(|edit| matches!(edit, Edit::Deletion(_)))
+    }
+
+    #[inline]
+    fn count<T: Fn(&Edit) -> bool>(&self, predicate: T) -> usize {
+        self.edits.iter().filter(|edit| predicate(edit)).count()
+    }
+
+    /// Side by side listing with the instruction offsets in each genome.
+    /// Matches are only listed when `matches` is set.
+    pub fn side_by_side(&self, matches: bool) -> SideBySide<'_> {
+        SideBySide { alignment: self, matches }
+    }
+}
+
+pub struct SideBySide<'a> {
+    alignment: &'a Alignment,
+    matches: bool,
+}
+
+impl<'a> fmt::Display for SideBySide<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let (mut i, mut j) = (0, 0);
+        for edit in self.alignment.edits.iter() {
+            match edit {
+                Edit::Match(instruction) => {
+                    if self.matches {
+                        writeln!(f, "{:>5} {:<6}   {:<6} {:>5}", i, instruction, instruction, j)?;
+                    }
+                    i += 1;
+                    j += 1;
+                },
+                Edit::Substitution(from, to) => {
+                    writeln!(f, "{:>5} {:<6} * {:<6} {:>5}", i, from, to, j)?;
+                    i += 1;
+                    j += 1;
+                },
+                Edit::Deletion(from) => {
+                    writeln!(f, "{:>5} {:<6} - {:<6} {:>5}", i, from, "", "")?;
+                    i += 1;
+                },
+                Edit::Insertion(to) => {
+                    writeln!(f, "{:>5} {:<6} + {:<6} {:>5}", "", "", to, j)?;
+                    j += 1;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "edit distance {} (substitutions {}, insertions {}, deletions {}), hamming distance {}",
+               self.edit_distance,
+               self.substitutions(),
+               self.insertions(),
+               self.deletions(),
+               self.hamming_distance)
+    }
+}
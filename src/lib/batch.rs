@@ -0,0 +1,132 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use config::Config;
+use random_generator::RandomGenerator;
+use simulation::{Sample, Simulation};
+
+/// Independent runs of the same configuration, replicate `i` being seeded
+/// with `seed + i`.
+pub struct Batch {
+    pub config: Config,
+    pub replicates: usize,
+    pub ticks: usize,
+    pub threads: usize,
+    pub seed: usize,
+}
+
+impl Batch {
+    /// Runs every replicate on a pool of `threads` threads, writing its
+    /// reports to the writer `create` returns for its index. Returns the
+    /// samples of every replicate, in replicate order.
+    pub fn run<W, F>(&self, create: F) -> io::Result<Vec<Vec<Sample>>>
+        where W: Write, F: Fn(usize) -> io::Result<W> + Sync {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(self.replicates));
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.clamp(1, self.replicates.max(1)))
+                .map(|_| scope.spawn(|| -> io::Result<()> {
+                    loop {
+                        let replicate = next.fetch_add(1, Ordering::SeqCst);
+                        if replicate >= self.replicates {
+                            return Ok(());
+                        }
+                        let samples = self.run_replicate(replicate, create(replicate)?)?;
+                        results.lock().expect("A replicate panicked").push((replicate, samples));
+                    }
+                }))
+                .collect();
+            workers.into_iter()
+                .try_for_each(|worker| worker.join().expect("A replicate panicked"))
+        })?;
+        let mut results = results.into_inner().expect("A replicate panicked");
+        results.sort_by_key(|(replicate, _)| *replicate);
+        Ok(results.into_iter().map(|(_, samples)| samples).collect())
+    }
+
+    fn run_replicate<W: Write>(&self, replicate: usize, mut writer: W) -> io::Result<Vec<Sample>> {
+        let seed = self.seed.wrapping_add(replicate);
+        let mut simulation = Simulation::new(self.config.clone(), RandomGenerator::from_seed(self.config.rng, seed as u64));
+        let mut samples = Vec::new();
+        let mut result = Ok(());
+        while simulation.statistics.clock < self.ticks && result.is_ok() {
+            simulation.tick(|simulation| {
+                let sample = simulation.sample();
+                result = if simulation.config.schedules.is_empty() {
+                    writeln!(writer, "{},{},{}", sample, simulation.statistics, sample.metabolism)
+                } else {
+                    writeln!(writer, "{},{},{},{}",
+                             sample, simulation.statistics, sample.metabolism, simulation.parameters())
+                };
+                samples.push(sample);
+                simulation.statistics.zero();
+            });
+        }
+        result?;
+        writer.flush()?;
+        Ok(samples)
+    }
+}
+
+struct Moments {
+    mean: f64,
+    stddev: f64,
+}
+
+impl Moments {
+    fn new<I: Iterator<Item=usize>>(values: I) -> Moments {
+        let values: Vec<f64> = values.map(|value| value as f64).collect();
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = if values.len() > 1 {
+            values.iter().map(|value| (value - mean) * (value - mean)).sum::<f64>() / (count - 1.0)
+        } else {
+            0.0
+        };
+        Moments { mean, stddev: variance.sqrt() }
+    }
+}
+
+impl fmt::Display for Moments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{},{}", self.mean, self.stddev)
+    }
+}
+
+/// Mean and sample standard deviation over the replicates of the pond
+/// totals at every report, as CSV with a header.
+pub struct Summary<'a> {
+    replicates: &'a [Vec<Sample>],
+}
+
+impl<'a> Summary<'a> {
+    pub fn new(replicates: &'a [Vec<Sample>]) -> Summary<'a> {
+        Summary { replicates }
+    }
+}
+
+impl<'a> fmt::Display for Summary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(f, "clock,replicates,\
+                     energy_mean,energy_stddev,\
+                     active_cells_mean,active_cells_stddev,\
+                     viable_replicators_mean,viable_replicators_stddev,\
+                     max_generation_mean,max_generation_stddev,\
+                     metabolism_mean,metabolism_stddev")?;
+        let reports = self.replicates.iter().map(|samples| samples.len()).min().unwrap_or(0);
+        for report in 0..reports {
+            let samples: Vec<&Sample> = self.replicates.iter().map(|samples| &samples[report]).collect();
+            writeln!(f, "{},{},{},{},{},{},{}",
+                     samples[0].clock,
+                     samples.len(),
+                     Moments::new(samples.iter().map(|sample| sample.total_energy)),
+                     Moments::new(samples.iter().map(|sample| sample.active_cells)),
+                     Moments::new(samples.iter().map(|sample| sample.viable_replicators)),
+                     Moments::new(samples.iter().map(|sample| sample.max_generation)),
+                     Moments::new(samples.iter().map(|sample| sample.metabolism)))?;
+        }
+        Ok(())
+    }
+}
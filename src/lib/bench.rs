@@ -0,0 +1,126 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use config::Config;
+use random_generator::RandomGenerator;
+use simulation::{Phase, Simulation, TickHooks};
+
+/// A run of `ticks` ticks from a fixed seed, timed phase by phase. Reports
+/// are formatted like those of a run and thrown away.
+pub struct Benchmark {
+    pub config: Config,
+    pub seed: usize,
+    pub ticks: usize,
+}
+
+impl Benchmark {
+    pub fn run(&self) -> BenchmarkReport {
+        let start = Instant::now();
+        let mut simulation = Simulation::new(self.config.clone(), RandomGenerator::from_seed(self.config.rng, self.seed as u64));
+        let setup = start.elapsed();
+        let mut report = BenchmarkReport {
+            config: self.config.clone(),
+            seed: self.seed,
+            ticks: self.ticks,
+            cell_executions: 0,
+            instruction_executions: 0,
+            setup,
+            total: Duration::default(),
+            reporting: Duration::default(),
+            inflow: Duration::default(),
+        };
+        let mut sink = io::sink();
+        let mut timer = PhaseTimer::default();
+        let start = Instant::now();
+        while simulation.statistics.clock < self.ticks {
+            simulation.tick_with(|simulation| {
+                let sample = simulation.sample();
+                write!(sink, "{},{},{}", sample, simulation.statistics, sample.metabolism)
+                    .expect("Can't write to a sink");
+                report.count(simulation);
+                simulation.statistics.zero();
+            }, &mut timer);
+        }
+        report.total = start.elapsed();
+        report.reporting = timer.reporting;
+        report.inflow = timer.inflow;
+        report.count(&simulation);
+        report
+    }
+}
+
+/// Adds up the time spent in the report and inflow phases of the ticks.
+#[derive(Default)]
+struct PhaseTimer {
+    started: Option<Instant>,
+    reporting: Duration,
+    inflow: Duration,
+}
+
+impl TickHooks for PhaseTimer {
+    #[inline]
+    fn enter(&mut self, _phase: Phase) {
+        self.started = Some(Instant::now());
+    }
+
+    #[inline]
+    fn leave(&mut self, phase: Phase) {
+        let elapsed = self.started.take().map_or(Duration::default(), |started| started.elapsed());
+        match phase {
+            Phase::Report => self.reporting += elapsed,
+            Phase::Inflow => self.inflow += elapsed,
+        }
+    }
+}
+
+/// Throughput of a benchmark, as CSV with a header. The time spent in the
+/// VM is whatever is not spent reporting or in inflows.
+pub struct BenchmarkReport {
+    pub config: Config,
+    pub seed: usize,
+    pub ticks: usize,
+    pub cell_executions: usize,
+    pub instruction_executions: usize,
+    pub setup: Duration,
+    pub total: Duration,
+    pub reporting: Duration,
+    pub inflow: Duration,
+}
+
+impl BenchmarkReport {
+    #[inline]
+    fn count(&mut self, simulation: &Simulation) {
+        self.cell_executions += simulation.statistics.cell_executions();
+        self.instruction_executions += simulation.statistics.instruction_executions();
+    }
+
+    #[inline]
+    pub fn vm(&self) -> Duration {
+        self.total.saturating_sub(self.reporting + self.inflow)
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let seconds = self.total.as_secs_f64();
+        writeln!(f, "pond_width,pond_height,scheduling,seed,ticks,\
+                     cell_executions,instruction_executions,\
+                     cells_per_second,instructions_per_second,\
+                     setup_seconds,total_seconds,vm_seconds,reporting_seconds,inflow_seconds")?;
+        writeln!(f, "{},{},{},{},{},{},{},{:.0},{:.0},{:.6},{:.6},{:.6},{:.6},{:.6}",
+                 self.config.pond_width,
+                 self.config.pond_height,
+                 self.config.scheduling,
+                 self.seed,
+                 self.ticks,
+                 self.cell_executions,
+                 self.instruction_executions,
+                 self.cell_executions as f64 / seconds,
+                 self.instruction_executions as f64 / seconds,
+                 self.setup.as_secs_f64(),
+                 seconds,
+                 self.vm().as_secs_f64(),
+                 self.reporting.as_secs_f64(),
+                 self.inflow.as_secs_f64())
+    }
+}
@@ -1,28 +1,72 @@
+use std::fmt;
 use genome::Genome;
-use super::RandomGenerator;
+use genome_store::GenomeBuffer;
+use rng::Rng;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct CellPosition(pub(crate) usize, pub(crate) usize);
 
-#[derive(Clone, PartialEq)]
-pub struct CellId(usize);
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CellId(pub(crate) usize);
+
+impl fmt::Display for CellId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
 
 pub struct CellIdGenerator {
-    current: usize,
+    pub(crate) current: usize,
+    step: usize,
+}
+
+impl Default for CellIdGenerator {
+    fn default() -> CellIdGenerator {
+        CellIdGenerator::new()
+    }
 }
 
 impl CellIdGenerator {
     #[inline]
     pub fn new() -> CellIdGenerator {
+        CellIdGenerator::starting_at(0)
+    }
+
+    #[inline]
+    pub(crate) fn starting_at(current: usize) -> CellIdGenerator {
         CellIdGenerator {
-            current: 0,
+            current,
+            step: 1,
         }
     }
 
     #[inline]
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> CellId {
-        self.current += 1;
+        self.current += self.step;
         CellId(self.current)
     }
+
+    /// Splits the generator into `count` + 1 generators that hand out
+    /// disjoint ids: this one keeps the first stream and the others are
+    /// returned, so they can be used concurrently.
+    pub fn interleave(&mut self, count: usize) -> Vec<CellIdGenerator> {
+        let step = self.step * (count + 1);
+        let others = (1..count + 1)
+            .map(|i| CellIdGenerator {
+                current: self.current + i * self.step,
+                step,
+            })
+            .collect();
+        self.step = step;
+        others
+    }
+
+    /// Sequential generator following the ids handed out by all of
+    /// `generators`.
+    pub fn after<'a, I: Iterator<Item=&'a CellIdGenerator>>(generators: I) -> CellIdGenerator {
+        CellIdGenerator::starting_at(generators.map(|generator| generator.current).max().unwrap_or(0))
+    }
 }
 
 pub(crate) enum InteractionType {
@@ -30,48 +74,58 @@ pub(crate) enum InteractionType {
     Positive,
 }
 
-pub struct Cell {
+#[derive(Clone)]
+pub struct Cell<G: GenomeBuffer = Genome> {
     pub(crate) id: CellId,
     pub(crate) parent_id: Option<CellId>,
     pub(crate) lineage: CellId,
     pub(crate) generation: usize,
     pub(crate) energy: usize,
-    pub(crate) genome: Genome,
+    pub(crate) genome: G,
 }
 
-impl Cell {
+impl<G: GenomeBuffer> Cell<G> {
     #[inline]
-    pub fn new(generator: &mut CellIdGenerator) -> Cell {
+    pub fn new(generator: &mut CellIdGenerator) -> Cell<G> {
         Cell {
             id: generator.next(),
             parent_id: None,
             lineage: CellId(0),
             generation: 0,
             energy: 0,
-            genome: Genome::new(),
+            genome: G::new(Genome::new()),
         }
     }
     #[inline]
-    pub fn random(
-        id_generator: &mut CellIdGenerator, generator: &mut RandomGenerator) -> Cell {
+    pub fn random<R: Rng>(id_generator: &mut CellIdGenerator, generator: &mut R) -> Cell<G> {
         let mut res = Cell::new(id_generator);
-        res.genome = Genome::random(generator);
+        res.genome = G::new(Genome::random(generator));
         res
     }
 
+    #[inline]
+    pub(crate) fn genome(&self) -> &Genome {
+        self.genome.genome()
+    }
+
+    #[inline]
+    pub(crate) fn genome_mut(&mut self) -> &mut Genome {
+        self.genome.genome_mut()
+    }
+
     #[inline]
     pub(crate) fn can_be_accessed(
         &self, guess: u8, interaction: InteractionType, threshold: u8) -> bool {
         match interaction {
             InteractionType::Positive => {
-                self.parent_id == None ||
+                self.parent_id.is_none() ||
                     (threshold & 0x0f) >=
-                        ((self.genome.0[0] & 0x0f) ^ (guess & 0x0f)).count_ones() as u8
+                        ((self.genome().0[0] & 0x0f) ^ (guess & 0x0f)).count_ones() as u8
             },
             InteractionType::Negative => {
-                self.parent_id == None ||
+                self.parent_id.is_none() ||
                     (threshold & 0x0f) <=
-                        ((self.genome.0[0] & 0x0f) ^ (guess & 0x0f)).count_ones() as u8
+                        ((self.genome().0[0] & 0x0f) ^ (guess & 0x0f)).count_ones() as u8
             }
         }
     }
@@ -1,109 +1,531 @@
+use std::io;
+use std::sync::Arc;
+use active_cells::ActiveCells;
+use aggregates::{Aggregates, Contribution};
 use cell::{Cell, CellId, CellIdGenerator, CellPosition};
-use cell_vm::Facing;
+use config::Config;
+use disturbance::{boosted_mutation_rate, MutationBoost};
+use events::{EventKind, EventLog, Journal};
 use genome::Genome;
-use random_generator::RandomGenerator;
-use super::{INFLOW_RATE_BASE, POND_HEIGHT, POND_WIDTH};
+use genome_store::{GenomeBuffer, GenomeStore};
+use genome_analysis::ClassCensus;
+use rng::Rng;
+use mask::Mask;
+use topology::{Boundary, Facing, Topology};
 
-pub struct CellPond {
-    grind: Vec<Vec<Cell>>,
+/// Access to the cells a `CellVM` works on.
+pub trait Grid {
+    type Buffer: GenomeBuffer;
+
+    fn cell(&mut self, position: &CellPosition) -> &mut Cell<Self::Buffer>;
+
+    /// The cell `facing` points to, unless it is beyond a wall or an
+    /// obstacle.
+    fn neighbor_position(&self, position: &CellPosition, facing: &Facing) -> Option<CellPosition>;
+
+    /// The mutation rate of the cell at `position`.
+    fn mutation_rate_at(&self, position: &CellPosition) -> usize;
+
+    fn topology(&self) -> Topology;
+
+    /// The buffer a newly born cell gets for `genome`.
+    #[inline]
+    fn share(&mut self, genome: &Genome) -> Self::Buffer {
+        Self::Buffer::new(genome.clone())
+    }
+
+    #[inline]
+    fn get_neighbor(&mut self, position: &CellPosition, facing: &Facing) -> Option<&mut Cell<Self::Buffer>> {
+        let neighbor = self.neighbor_position(position, facing)?;
+        Some(self.cell(&neighbor))
+    }
 }
 
-impl CellPond {
-    pub fn new(id_generator: &mut CellIdGenerator, generator: &mut RandomGenerator) -> CellPond {
-        let mut grind = Vec::with_capacity(POND_WIDTH);
-        for i in 0..POND_WIDTH {
-            grind.push(Vec::with_capacity(POND_HEIGHT));
-            for _ in 0..POND_HEIGHT {
-                grind[i].push(Cell::random(id_generator, generator));
+/// The cells live in one contiguous row-major array, the cell at `(x, y)`
+/// being at index `y * width + x`. Cells hold their genome in a `G`, see
+/// `GenomeBuffer`.
+pub struct CellPond<G: GenomeBuffer = Genome> {
+    cells: Vec<Cell<G>>,
+    width: usize,
+    height: usize,
+    inflow_rate: usize,
+    mutation_rate: usize,
+    topology: Topology,
+    boundary: Boundary,
+    obstacles: Option<Arc<[bool]>>,
+    boosts: Vec<MutationBoost>,
+    genome_store: Option<GenomeStore>,
+    aggregates: Aggregates,
+    touched: Vec<(usize, Contribution)>,
+    /// Whether each cell is in `touched`, so that it is added only once.
+    dirty: Vec<bool>,
+    active_cells: Option<ActiveCells>,
+    journal: Option<Journal<G>>,
+}
+
+impl<G: GenomeBuffer> Clone for CellPond<G> {
+    fn clone(&self) -> CellPond<G> {
+        CellPond {
+            cells: self.cells.clone(),
+            width: self.width,
+            height: self.height,
+            inflow_rate: self.inflow_rate,
+            mutation_rate: self.mutation_rate,
+            topology: self.topology,
+            boundary: self.boundary,
+            obstacles: self.obstacles.clone(),
+            boosts: self.boosts.clone(),
+            genome_store: None,
+            aggregates: self.aggregates.clone(),
+            touched: self.touched.clone(),
+            dirty: self.dirty.clone(),
+            active_cells: self.active_cells.clone(),
+            journal: None,
+        }
+    }
+}
+
+impl<G: GenomeBuffer> CellPond<G> {
+    pub fn new<R: Rng>(id_generator: &mut CellIdGenerator, generator: &mut R) -> CellPond<G> {
+        CellPond::with_config(&Config::default(), id_generator, generator)
+    }
+
+    pub fn with_config<R: Rng>(config: &Config,
+                               id_generator: &mut CellIdGenerator,
+                               generator: &mut R) -> CellPond<G> {
+        // Cells are created column by column, as they always were, so that a
+        // seed still gives the same pond.
+        let cells = (0..config.pond_width * config.pond_height)
+            .map(|_| Cell::random(id_generator, generator));
+        CellPond::from_cells(config, cells)
+    }
+
+    /// Builds a pond from its cells listed column by column.
+    pub(crate) fn from_cells<I: IntoIterator<Item=Cell<G>>>(config: &Config, cells: I) -> CellPond<G> {
+        let (width, height) = (config.pond_width, config.pond_height);
+        let mut pond = CellPond {
+            cells: vec![Cell::new(&mut CellIdGenerator::new()); width * height],
+            width: config.pond_width,
+            height: config.pond_height,
+            inflow_rate: config.inflow_rate_base,
+            mutation_rate: config.mutation_rate,
+            topology: config.topology,
+            boundary: config.boundary,
+            obstacles: None,
+            boosts: Vec::new(),
+            genome_store: None,
+            aggregates: Aggregates::default(),
+            touched: Vec::with_capacity(4),
+            dirty: vec![false; width * height],
+            active_cells: None,
+            journal: None,
+        };
+        for (index, cell) in cells.into_iter().enumerate() {
+            pond.cells[(index % height) * width + index / height] = cell;
+        }
+        pond.aggregates = Aggregates::scan(pond.cells.iter());
+        pond
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    #[inline]
+    pub fn inflow_rate(&self) -> usize {
+        self.inflow_rate
+    }
+
+    #[inline]
+    pub fn set_inflow_rate(&mut self, inflow_rate: usize) {
+        self.inflow_rate = inflow_rate;
+    }
+
+    /// The mutation rate outside of boosts.
+    #[inline]
+    pub fn mutation_rate(&self) -> usize {
+        self.mutation_rate
+    }
+
+    #[inline]
+    pub fn set_mutation_rate(&mut self, mutation_rate: usize) {
+        self.mutation_rate = mutation_rate;
+    }
+
+    #[inline]
+    pub(crate) fn boost_mutation(&mut self, boost: MutationBoost) {
+        self.boosts.push(boost);
+    }
+
+    /// Ends the boosts that last until `clock`.
+    #[inline]
+    pub(crate) fn expire_boosts(&mut self, clock: usize) {
+        self.boosts.retain(|boost| boost.until > clock);
+    }
+
+    #[inline]
+    pub(crate) fn boosts(&self) -> &[MutationBoost] {
+        &self.boosts
+    }
+
+    /// Turns the cells set in `mask` into obstacles: they lose their energy
+    /// and are never reached by a neighbor or an inflow again.
+    pub fn place_obstacles(&mut self, mask: &Mask) -> Result<(), String> {
+        mask.fits(self.width, self.height)?;
+        let mut obstacles = vec![false; self.width * self.height];
+        for (position, _) in mask.cells().filter(|(_, value)| *value > 0) {
+            let index = self.index(&position);
+            obstacles[index] = true;
+            self.bulk_cell(index).energy = 0;
+        }
+        self.rescan();
+        self.obstacles = Some(obstacles.into());
+        Ok(())
+    }
+
+    /// Gives every cell but the obstacles its level in `mask` of the energy
+    /// of an inflow.
+    pub fn set_energy(&mut self, mask: &Mask) -> Result<(), String> {
+        mask.fits(self.width, self.height)?;
+        let inflow_rate = self.inflow_rate as f64;
+        for (position, _) in mask.cells() {
+            if !self.is_obstacle(&position) {
+                let index = self.index(&position);
+                self.bulk_cell(index).energy = (mask.level(&position) * inflow_rate).round() as usize;
             }
         }
-        CellPond {
-            grind,
+        self.rescan();
+        Ok(())
+    }
+
+    /// Replaces the genome of every cell with a non-zero value `n` in `mask`
+    /// by `genomes[n - 1]`, as a new cell keeping the energy of the old one.
+    pub fn place_genomes(&mut self,
+                         mask: &Mask,
+                         genomes: &[Genome],
+                         id_generator: &mut CellIdGenerator) -> Result<(), String> {
+        mask.fits(self.width, self.height)?;
+        if let Some((_, value)) = mask.cells().find(|(_, value)| usize::from(*value) > genomes.len()) {
+            return Err(format!("The mask places genome {} but only {} are given", value, genomes.len()));
+        }
+        for (position, value) in mask.cells().filter(|(_, value)| *value > 0) {
+            if !self.is_obstacle(&position) {
+                let id = id_generator.next();
+                let genome = self.share(&genomes[usize::from(value) - 1]);
+                let index = self.index(&position);
+                let cell = self.bulk_cell(index);
+                cell.id = id.clone();
+                cell.parent_id = None;
+                cell.lineage = id;
+                cell.generation = 0;
+                cell.genome = genome;
+            }
         }
+        self.rescan();
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_obstacle(&self, position: &CellPosition) -> bool {
+        is_obstacle(&self.obstacles, self.index(position))
+    }
+
+    #[inline]
+    pub(crate) fn obstacles(&self) -> Option<Arc<[bool]>> {
+        self.obstacles.clone()
+    }
+
+    /// Number of distinct genomes handed out to newborn cells that some
+    /// cell still holds, if genomes are shared. The store is pruned first.
+    #[inline]
+    pub fn shared_genomes(&mut self) -> Option<usize> {
+        self.genome_store.as_mut().map(|store| {
+            store.prune();
+            store.len()
+        })
     }
 
+    #[inline]
+    pub(crate) fn take_genome_store(&mut self) -> Option<GenomeStore> {
+        self.genome_store.take()
+    }
+
+    #[inline]
+    pub(crate) fn restore_genome_store(&mut self, genome_store: Option<GenomeStore>) {
+        self.genome_store = genome_store;
+    }
+
+    /// Keeps track of every change to the pond and writes it to `log` as
+    /// events, grouped by `commit`.
+    #[inline]
+    pub fn record_events(&mut self, log: EventLog) {
+        self.journal = Some(Journal::new(log, self.cells.len()));
+    }
+
+    /// Ends a tick: the pond totals are brought up to date with the cells
+    /// changed since the last commit, and the changes are recorded if
+    /// events are.
+    #[inline]
+    pub fn commit(&mut self, tick: usize, kind: EventKind) -> io::Result<()> {
+        self.update_aggregates();
+        if let Some(ref mut journal) = self.journal {
+            let (cells, width) = (&self.cells, self.width);
+            journal.commit(tick, kind, |position| &cells[position.1 * width + position.0])?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn update_aggregates(&mut self) {
+        for (index, before) in self.touched.drain(..) {
+            self.dirty[index] = false;
+            let after = Contribution::from(&self.cells[index]);
+            if let Some(ref mut active_cells) = self.active_cells {
+                active_cells.update(index, before.is_active(), after.is_active());
+            }
+            self.aggregates.update(before, after);
+        }
+    }
+
+    /// The cell at `index` for a change of many cells at once. The change
+    /// is journaled but left out of the totals until `rescan`.
+    #[inline]
+    fn bulk_cell(&mut self, index: usize) -> &mut Cell<G> {
+        if let Some(ref mut journal) = self.journal {
+            journal.touch(index, &CellPosition(index % self.width, index / self.width), &self.cells[index]);
+        }
+        &mut self.cells[index]
+    }
+
+    /// Rebuilds the totals and the index of active cells with one pass over
+    /// the pond, after a change of many cells at once.
+    fn rescan(&mut self) {
+        for (index, _) in self.touched.drain(..) {
+            self.dirty[index] = false;
+        }
+        self.aggregates = Aggregates::scan(self.cells.iter());
+        if self.active_cells.is_some() {
+            self.index_active_cells();
+        }
+    }
+
+    /// Keeps an index of the cells with energy, see `Scheduling::Active`.
+    pub(crate) fn index_active_cells(&mut self) {
+        self.active_cells = Some(ActiveCells::new(self.cells.iter().map(|cell| cell.energy > 0)));
+    }
+
+    /// The number of cells with energy, if they are indexed.
+    #[inline]
+    pub(crate) fn indexed_active_cells(&self) -> Option<usize> {
+        self.active_cells.as_ref().map(|active_cells| active_cells.len())
+    }
+
+    #[inline]
+    pub(crate) fn active_cell_position(&self, slot: usize) -> CellPosition {
+        let index = self.active_cells.as_ref().expect("Active cells aren't indexed").get(slot);
+        CellPosition(index % self.width, index / self.width)
+    }
+
+    /// Adds the changes gathered by an executor that bypassed `cell`.
+    #[inline]
+    pub(crate) fn merge_aggregates(&mut self, changes: &mut Aggregates) {
+        self.aggregates.merge(changes);
+    }
+
+    /// Number of events recorded so far, 0 if they aren't.
+    #[inline]
+    pub fn recorded_events(&self) -> usize {
+        self.journal.as_ref().map_or(0, |journal| journal.written())
+    }
+
+    #[inline]
+    pub fn flush_events(&mut self) -> io::Result<()> {
+        match self.journal {
+            Some(ref mut journal) => journal.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the cell at `position` a new cell with `genome`, adding the
+    /// energy of an inflow to its own. Like an execution, the change is
+    /// only counted once the caller commits it, see `commit`.
     #[inline]
     pub fn replace(&mut self, position: &CellPosition, new_id: CellId, genome: Genome) {
-        let cell = &mut self.grind[position.0][position.1];
+        let inflow_rate = self.inflow_rate;
+        let cell = self.cell(position);
         cell.id = new_id.clone();
         cell.parent_id = None;
         cell.lineage = new_id;
         cell.generation = 0;
-        cell.energy += INFLOW_RATE_BASE;
-        cell.genome = genome;
+        cell.energy += inflow_rate;
+        cell.genome = G::new(genome);
+    }
+
+    /// Empties the cells at `positions`: each becomes a new cell with no
+    /// energy and the blank `genome`. The totals are rebuilt with one pass
+    /// over the pond rather than cell by cell.
+    pub(crate) fn wipe(&mut self,
+                       positions: &[CellPosition],
+                       id_generator: &mut CellIdGenerator,
+                       genome: &G) {
+        for position in positions {
+            let new_id = id_generator.next();
+            let index = self.index(position);
+            let cell = self.bulk_cell(index);
+            cell.id = new_id.clone();
+            cell.parent_id = None;
+            cell.lineage = new_id;
+            cell.generation = 0;
+            cell.energy = 0;
+            cell.genome = genome.clone();
+        }
+        self.rescan();
     }
 
     #[inline]
-    pub(crate) fn cell(&mut self, position: &CellPosition) -> &mut Cell {
-        &mut self.grind[position.0][position.1]
+    fn index(&self, position: &CellPosition) -> usize {
+        position.1 * self.width + position.0
     }
 
     #[inline]
-    pub(crate) fn get_neighbor(&mut self, position: &CellPosition, facing: &Facing) -> &mut Cell {
-        match facing {
-            Facing::Left => {
-                let x = if position.0 == 0 {
-                    POND_WIDTH-1
-                } else {
-                    position.0
-                };
-                &mut self.grind[x][position.1]
-            },
-            Facing::Right => {
-                let x = (position.0 + 1) % POND_WIDTH;
-                &mut self.grind[x][position.1]
-            },
-            Facing::Up => {
-                let y = (position.1 + 1) % POND_HEIGHT;
-                &mut self.grind[position.0][y]
-            },
-            Facing::Down => {
-                let y = if position.1 == 0 {
-                    POND_HEIGHT-1
-                } else {
-                    position.1-1
-                };
-                &mut self.grind[position.0][y]
-            },
+    pub(crate) fn cell(&mut self, position: &CellPosition) -> &mut Cell<G> {
+        let index = self.index(position);
+        if !self.dirty[index] {
+            self.dirty[index] = true;
+            self.touched.push((index, Contribution::from(&self.cells[index])));
+        }
+        if let Some(ref mut journal) = self.journal {
+            journal.touch(index, position, &self.cells[index]);
         }
+        &mut self.cells[index]
+    }
+
+    #[inline]
+    pub(crate) fn cell_at(&mut self, x: usize, y: usize) -> &mut Cell<G> {
+        self.cell(&CellPosition(x, y))
+    }
+
+    /// Raw pointer to the cells, for executors that hand out disjoint
+    /// parts of the pond to several threads.
+    pub(crate) fn cells_pointer(&mut self) -> *mut Cell<G> {
+        assert!(self.journal.is_none(), "Events can't be recorded from several threads");
+        assert!(self.active_cells.is_none(), "Active cells can't be indexed from several threads");
+        self.cells.as_mut_ptr()
+    }
+
+    /// Every cell, column by column.
+    #[inline]
+    pub(crate) fn cells<'a>(&'a self) -> impl Iterator<Item=&'a Cell<G>> + 'a {
+        let (width, height) = (self.width, self.height);
+        (0..width).flat_map(move |x| (0..height).map(move |y| &self.cells[y * width + x]))
     }
 
     #[inline]
     pub fn total_energy(&self) -> usize {
-        self.perform_on_active(0, |acc, cell| cell.energy + acc)
+        self.aggregates().total_energy()
     }
 
     #[inline]
     pub fn total_active_cells(&self) -> usize {
-        self.perform_on_active(0, |acc, _| acc+1)
+        self.aggregates().active_cells()
     }
 
     #[inline]
     pub fn total_viable_replicators(&self) -> usize {
-        self.perform_on_active(
-            0, |acc, cell| if cell.generation > 2 { acc + 1 } else { acc })
+        self.aggregates().viable_replicators()
     }
 
     #[inline]
     pub fn max_generation(&self) -> usize {
-        self.perform_on_active(0, |g, cell|
-            if cell.generation > g {
-                cell.generation
-            } else {
-                g
-            })
+        self.aggregates().max_generation()
+    }
+
+    /// The totals as of the last commit, checked against a full scan in
+    /// debug builds.
+    #[inline]
+    fn aggregates(&self) -> &Aggregates {
+        debug_assert!(self.touched.is_empty(), "Pond totals read in the middle of a tick");
+        debug_assert_eq!(self.aggregates, Aggregates::scan(self.cells.iter()));
+        &self.aggregates
+    }
+
+    #[inline]
+    pub fn class_census(&self) -> ClassCensus {
+        self.perform_on_active(ClassCensus::new(), |mut census, cell| {
+            census.add(cell);
+            census
+        })
+    }
+
+    #[inline]
+    pub(crate) fn active_cells<'a>(&'a self) -> impl Iterator<Item=&'a Cell<G>> + 'a {
+        self.cells().filter(|cell| cell.energy > 0)
     }
 
-    fn perform_on_active<R, T: Fn(R, &Cell) -> R>(&self, zero: R, op: T) -> R {
+    fn perform_on_active<R, T: Fn(R, &Cell<G>) -> R>(&self, zero: R, op: T) -> R {
         let mut acc = zero;
-        for x in 0..POND_WIDTH {
-            for y in 0..POND_HEIGHT {
-                let c = &self.grind[x][y];
-                if c.energy > 0 {
-                    acc = op(acc, c);
-                }
+        for c in &self.cells {
+            if c.energy > 0 {
+                acc = op(acc, c);
             }
         }
         acc
     }
-}
\ No newline at end of file
+}
+
+impl CellPond<Arc<Genome>> {
+    /// Lets cells born with the same genome share one buffer.
+    #[inline]
+    pub fn share_genomes(&mut self) {
+        self.genome_store.get_or_insert_with(GenomeStore::new);
+    }
+}
+
+impl<G: GenomeBuffer> Grid for CellPond<G> {
+    type Buffer = G;
+
+    #[inline]
+    fn cell(&mut self, position: &CellPosition) -> &mut Cell<G> {
+        CellPond::cell(self, position)
+    }
+
+    #[inline]
+    fn neighbor_position(&self, position: &CellPosition, facing: &Facing) -> Option<CellPosition> {
+        self.topology.neighbor_position(position, facing, self.width, self.height, self.boundary)
+            .filter(|neighbor| !self.is_obstacle(neighbor))
+    }
+
+    #[inline]
+    fn mutation_rate_at(&self, position: &CellPosition) -> usize {
+        boosted_mutation_rate(&self.boosts, self.mutation_rate, position, self.width, self.height)
+    }
+
+    #[inline]
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    #[inline]
+    fn share(&mut self, genome: &Genome) -> G {
+        match self.genome_store {
+            Some(ref mut store) => G::intern(store, genome),
+            None => G::new(genome.clone()),
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn is_obstacle(obstacles: &Option<Arc<[bool]>>, index: usize) -> bool {
+    obstacles.as_ref().is_some_and(|obstacles| obstacles[index])
+}
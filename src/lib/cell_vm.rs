@@ -1,34 +1,18 @@
 use cell::{CellIdGenerator, CellPosition, InteractionType};
-use cell_pond::CellPond;
+use cell_pond::{CellPond, Grid};
 use instruction::Instruction;
 use genome::{Genome, GenomePointer};
-use random_generator::RandomGenerator;
+use random_generator::{RandomGenerator, RandomStreams};
+use rng::Rng;
+use reproduction_log::ReproductionRecord;
 use statistics::Statistics;
-use super::{FAILED_KILL_PENALTY, MUTATION_RATE, POND_DEPTH};
+use topology::Facing;
+use super::{FAILED_KILL_PENALTY, POND_DEPTH};
 
-pub(crate) enum Facing {
-    Up,
-    Down,
-    Right,
-    Left,
-}
-
-impl From<u8> for Facing {
-    fn from(byte: u8) -> Facing {
-        match byte & 0x3 {
-            0x0 => Facing::Left,
-            0x1 => Facing::Right,
-            0x2 => Facing::Up,
-            0x3 => Facing::Down,
-            _ => panic!("Can't happen"),
-        }
-    }
-}
-
-pub struct CellVM<'a> {
-    pond: &'a mut CellPond,
+pub struct CellVM<'a, P: 'a + Grid = CellPond, R: 'a + Rng = RandomGenerator> {
+    pond: &'a mut P,
     id_generator: &'a mut CellIdGenerator,
-    random_generator: &'a mut RandomGenerator,
+    random: &'a mut RandomStreams<R>,
     statistics: &'a mut Statistics,
     cell: CellPosition,
     output_pointer: GenomePointer,
@@ -36,21 +20,28 @@ pub struct CellVM<'a> {
     register: u8,
     output: Genome,
     facing: Facing,
+    mutation_rate: u64,
     running: bool,
     loop_stack: Vec<GenomePointer>,
     loop_stack_depth: usize,
+    lineage: Option<u64>,
 }
 
-impl<'a> CellVM<'a> {
+impl<'a, P: 'a + Grid, R: 'a + Rng> CellVM<'a, P, R> {
+    /// A VM for the cell at `cell`. On a `CellPond`, what an execution
+    /// changes only reaches the pond totals, the index of active cells and
+    /// the events at the next `CellPond::commit`, which is up to the
+    /// caller, once per execution.
     pub fn new(cell: CellPosition,
-               pond: &'a mut CellPond,
+               pond: &'a mut P,
                id_generator: &'a mut CellIdGenerator,
-               random_generator: &'a mut RandomGenerator,
-               statistics: &'a mut Statistics) -> CellVM<'a> {
+               random: &'a mut RandomStreams<R>,
+               statistics: &'a mut Statistics) -> CellVM<'a, P, R> {
+        let mutation_rate = pond.mutation_rate_at(&cell) as u64;
         CellVM {
             pond,
             id_generator,
-            random_generator,
+            random,
             statistics,
             cell,
             output_pointer: GenomePointer::new(0, true),
@@ -58,21 +49,42 @@ impl<'a> CellVM<'a> {
             register: 0,
             output: Genome::new(),
             facing: Facing::Left,
+            mutation_rate,
             running: true,
             loop_stack: Vec::with_capacity(POND_DEPTH),
             loop_stack_depth: 0,
+            lineage: None,
         }
     }
 
     pub fn execute(&mut self) {
         self.statistics.cell_executions += 1;
+        if let Some(ref mut positions) = self.statistics.positions {
+            positions.add(&self.cell);
+        }
+        if let Some(ref mut ngrams) = self.statistics.ngrams {
+            ngrams.start();
+        }
+        if let Some(ref mut lineages) = self.statistics.lineages {
+            let cell = self.pond.cell(&self.cell);
+            if cell.energy > 0 {
+                let key = lineages.key(cell);
+                lineages.counters(key).cell_executions += 1;
+                self.lineage = Some(key);
+            }
+        }
+        let mut instructions = 0;
         while self.pond.cell(&self.cell).energy > 0 && self.running {
             self.maybe_mutate();
             self.pond.cell(&self.cell).energy -= 1;
-            let instruction_byte = self.pond.cell(&self.cell).genome.get(&self.input_pointer);
+            let instruction_byte = self.pond.cell(&self.cell).genome().get(&self.input_pointer);
             self.input_pointer.next();
             let instruction = Instruction::from(instruction_byte);
+            instructions += 1;
             self.statistics.instruction_executions[&instruction] += 1;
+            if let Some(ref mut ngrams) = self.statistics.ngrams {
+                ngrams.add(instruction);
+            }
             if self.loop_stack_depth == 0 {
                 self.execute_instruction(instruction);
             } else if instruction == Instruction::Loop {
@@ -81,6 +93,9 @@ impl<'a> CellVM<'a> {
                 self.loop_stack_depth -= 1;
             }
         }
+        if let (Some(key), Some(lineages)) = (self.lineage, self.statistics.lineages.as_mut()) {
+            lineages.counters(key).instruction_executions += instructions;
+        }
         self.maybe_reproduce();
     }
 
@@ -103,10 +118,10 @@ impl<'a> CellVM<'a> {
                 self.register = (self.register.wrapping_sub(1)) & 0x0f;
             },
             Instruction::ReadGenome => {
-                self.register = self.pond.cell(&self.cell).genome.get(&self.input_pointer);
+                self.register = self.pond.cell(&self.cell).genome().get(&self.input_pointer);
             },
             Instruction::WriteGenome => {
-                self.pond.cell(&self.cell).genome.set(&self.input_pointer, self.register);
+                self.pond.cell(&self.cell).genome_mut().set(&self.input_pointer, self.register);
             },
             Instruction::ReadBuffer => {
                 self.register = self.output.get(&self.output_pointer);
@@ -122,57 +137,67 @@ impl<'a> CellVM<'a> {
                 }
             },
             Instruction::Rep => {
-                if let Some(mut input_pointer) = self.loop_stack.pop() {
+                if let Some(input_pointer) = self.loop_stack.pop() {
                     if self.register > 0 {
                         self.input_pointer = input_pointer;
                     }
                 }
             },
             Instruction::Turn => {
-                self.facing = Facing::from(self.register);
+                if let Some(facing) = self.pond.topology().facing(self.register) {
+                    self.facing = facing;
+                }
             },
             Instruction::Xchg => {
                 let register = self.register;
-                self.register = self.pond.cell(&self.cell).genome.get(&self.input_pointer);
-                self.pond.cell(&self.cell).genome.set(&self.input_pointer, register);
+                self.register = self.pond.cell(&self.cell).genome().get(&self.input_pointer);
+                self.pond.cell(&self.cell).genome_mut().set(&self.input_pointer, register);
                 self.input_pointer.next();
             },
             Instruction::Share => {
-                if self.can_access_neighbor(InteractionType::Positive) {
-                    let total_energy = self.pond.cell(&self.cell).energy +
-                        self.pond.get_neighbor(&self.cell, &self.facing).energy;
+                if let Some(position) = self.accessible_neighbor(InteractionType::Positive) {
+                    let total_energy = self.pond.cell(&self.cell).energy + self.pond.cell(&position).energy;
                     let neighbor_energy = total_energy/2;
                     let cell_energy = total_energy - neighbor_energy;
                     {
-                        let neighbor = self.pond.get_neighbor(&self.cell, &self.facing);
+                        let neighbor = self.pond.cell(&position);
                         if neighbor.generation > 2 {
                             self.statistics.viable_cell_shares += 1;
                         }
+                        if let (Some(key), Some(lineages)) =
+                            (self.lineage, self.statistics.lineages.as_mut()) {
+                            lineages.counters(key).shares += 1;
+                            let neighbor_key = lineages.key(neighbor);
+                            lineages.counters(neighbor_key).shared += 1;
+                        }
                         neighbor.energy = neighbor_energy;
                     }
                     self.pond.cell(&self.cell).energy = cell_energy;
                 }
             },
             Instruction::Kill => {
-                if self.can_access_neighbor(InteractionType::Negative) {
-                    let neighbor = self.pond.get_neighbor(&self.cell, &self.facing);
+                if let Some(position) = self.accessible_neighbor(InteractionType::Negative) {
+                    let neighbor = self.pond.cell(&position);
                     if neighbor.generation > 2 {
                         self.statistics.viable_cells_killed += 1;
                     }
+                    if let (Some(key), Some(lineages)) =
+                        (self.lineage, self.statistics.lineages.as_mut()) {
+                        lineages.counters(key).kills += 1;
+                        let neighbor_key = lineages.key(neighbor);
+                        lineages.counters(neighbor_key).killed += 1;
+                    }
                     neighbor.id = self.id_generator.next();
-                    neighbor.genome.0[0] = !0;
-                    neighbor.genome.0[1] = !0;
+                    let genome = neighbor.genome_mut();
+                    genome.0[0] = !0;
+                    genome.0[1] = !0;
                     neighbor.parent_id = None;
                     neighbor.lineage = neighbor.id.clone();
                     neighbor.generation = 0;
                 } else {
                     let cell_energy = self.pond.cell(&self.cell).energy;
                     let penalty = cell_energy * FAILED_KILL_PENALTY;
-                    self.pond.cell(&self.cell).energy = if cell_energy <= penalty {
-                        0
-                    } else {
-                        cell_energy - penalty
-                    };
+                    self.pond.cell(&self.cell).energy = cell_energy.saturating_sub(penalty);
                 }
             },
             Instruction::Stop => {
@@ -181,20 +206,25 @@ impl<'a> CellVM<'a> {
         }
     }
 
+    /// The position of the neighbor, if there is one and it lets the cell
+    /// interact with it.
     #[inline]
-    fn can_access_neighbor(&mut self, interaction: InteractionType) -> bool {
-        self.pond.get_neighbor(&self.cell, &self.facing)
-            .can_be_accessed(
-                self.register, interaction, self.random_generator.generate_integer() as u8)
+    fn accessible_neighbor(&mut self, interaction: InteractionType) -> Option<CellPosition> {
+        let position = self.pond.neighbor_position(&self.cell, &self.facing)?;
+        let threshold = self.random.interaction.generate_integer() as u8;
+        if self.pond.cell(&position).can_be_accessed(self.register, interaction, threshold) {
+            Some(position)
+        } else {
+            None
+        }
     }
 
     #[inline]
     fn maybe_mutate(&mut self) {
-        if self.random_generator.generate_integer() < MUTATION_RATE {
-            let new_instruction = self.random_generator.generate_integer() as u8  & 0x0f;
-            if self.random_generator.generate_boolean() {
-                self.pond
-                    .cell(&self.cell).genome
+        if self.random.mutation.generate_integer() < self.mutation_rate {
+            let new_instruction = self.random.mutation.generate_integer() as u8  & 0x0f;
+            if self.random.mutation.generate_boolean() {
+                self.pond.cell(&self.cell).genome_mut()
                     .set(&self.input_pointer, new_instruction);
             } else {
                 self.register = new_instruction;
@@ -204,23 +234,97 @@ impl<'a> CellVM<'a> {
 
     #[inline]
     pub fn maybe_reproduce(&mut self) {
-        let neighbor_energy =
-            self.pond.get_neighbor(&self.cell, &self.facing).energy;
-        if neighbor_energy > 0 &&
-            self.output.0[0] != 0xff &&
-            self.can_access_neighbor(InteractionType::Negative) {
+        let neighbor_energy = self.pond.get_neighbor(&self.cell, &self.facing)
+            .map_or(0, |neighbor| neighbor.energy);
+        let accessible = if neighbor_energy > 0 && self.output.0[0] != 0xff {
+            self.accessible_neighbor(InteractionType::Negative)
+        } else {
+            None
+        };
+        if let Some(position) = accessible {
             let parent = self.pond.cell(&self.cell).id.clone();
             let lineage = self.pond.cell(&self.cell).lineage.clone();
             let generation = self.pond.cell(&self.cell).generation + 1;
-            let neighbor = self.pond.get_neighbor(&self.cell, &self.facing);
+            let record = if self.statistics.reproductions.is_some() {
+                let parent_genome = self.pond.cell(&self.cell).genome();
+                Some(ReproductionRecord {
+                    tick: self.statistics.clock,
+                    parent: parent.0,
+                    child: 0,
+                    x: position.0,
+                    y: position.1,
+                    generation,
+                    parent_genome: parent_genome.fingerprint(),
+                    child_genome: self.output.fingerprint(),
+                    hamming_distance: parent_genome.hamming_distance(&self.output),
+                })
+            } else {
+                None
+            };
+            let genome = self.pond.share(&self.output);
+            let neighbor = self.pond.cell(&position);
             if neighbor.generation > 2 {
                 self.statistics.viable_cell_replaced += 1;
             }
+            if let (Some(key), Some(lineages)) =
+                (self.lineage, self.statistics.lineages.as_mut()) {
+                lineages.counters(key).offspring += 1;
+                let neighbor_key = lineages.key(neighbor);
+                lineages.counters(neighbor_key).replaced += 1;
+            }
             neighbor.id = self.id_generator.next();
             neighbor.parent_id = Some(parent);
             neighbor.lineage = lineage;
             neighbor.generation = generation;
-            neighbor.genome = self.output.clone();
+            neighbor.genome = genome;
+            if let (Some(mut record), Some(log)) = (record, self.statistics.reproductions.as_mut()) {
+                record.child = neighbor.id.0;
+                log.record(&record);
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use events::EventKind;
+    use rng::RngKind;
+
+    const WIDTH: usize = 12;
+    const HEIGHT: usize = 10;
+
+    fn assert_totals(pond: &CellPond) {
+        assert_eq!(pond.total_energy(), pond.cells().map(|cell| cell.energy).sum::<usize>());
+        assert_eq!(pond.total_active_cells(), pond.active_cells().count());
+        assert_eq!(pond.total_viable_replicators(), pond.active_cells().filter(|cell| cell.generation > 2).count());
+        assert_eq!(pond.max_generation(), pond.active_cells().map(|cell| cell.generation).max().unwrap_or(0));
+        assert_eq!(pond.indexed_active_cells(), Some(pond.total_active_cells()));
+    }
+
+    #[test]
+    fn committed_executions_and_inflows_keep_the_totals() {
+        let config = Config { pond_width: WIDTH, pond_height: HEIGHT, ..Config::default() };
+        let mut random = RandomStreams::new(RandomGenerator::from_seed(RngKind::Xorshift, 5));
+        let mut id_generator = CellIdGenerator::new();
+        let mut statistics = Statistics::new();
+        let mut pond: CellPond = CellPond::with_config(&config, &mut id_generator, &mut random.inflow);
+        pond.index_active_cells();
+        for tick in 1..5000 {
+            if tick % 4 == 0 {
+                let position = random.inflow.generate_cell_position(WIDTH, HEIGHT);
+                let genome = Genome::random(&mut random.inflow);
+                pond.replace(&position, id_generator.next(), genome);
+                pond.commit(tick, EventKind::Inflow).unwrap();
+                assert_totals(&pond);
+            }
+            let position = random.selection.generate_cell_position(WIDTH, HEIGHT);
+            CellVM::new(position, &mut pond, &mut id_generator, &mut random, &mut statistics).execute();
+            pond.commit(tick, EventKind::Execution).unwrap();
+            assert_totals(&pond);
         }
+        assert_eq!(statistics.cell_executions(), 4999);
+        assert!(statistics.instruction_executions() > 0);
+        assert!(pond.max_generation() > 0);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,96 @@
+use std::io::{self, Read, Write};
+use cell::{Cell, CellId, CellIdGenerator};
+use cell_pond::CellPond;
+use config::Config;
+use codec::{read_header, read_u64, write_header, write_u64};
+use genome::Genome;
+use genome_store::GenomeBuffer;
+use super::GENOME_SIZE;
+
+const MAGIC: &[u8; 4] = b"RPCK";
+const VERSION: u32 = 2;
+
+/// Full pond state at a given tick. The file holds the `RPCK` magic, a
+/// little endian u32 version, the tick, the number of events of the run's
+/// log the state includes, the last cell id handed out, the pond
+/// dimensions and then every cell, column by column. Parameters other than
+/// the dimensions are not kept and take their default values.
+pub struct Checkpoint {
+    pub clock: usize,
+    /// A checkpoint taken in the middle of a tick includes only part of its
+    /// events.
+    pub events: usize,
+    pub id_generator: CellIdGenerator,
+    pub pond: CellPond,
+}
+
+impl Checkpoint {
+    pub fn write<W: Write, G: GenomeBuffer>(
+        writer: &mut W, clock: usize, events: usize, id_generator: &CellIdGenerator, pond: &CellPond<G>)
+        -> io::Result<()> {
+        write_header(writer, MAGIC, VERSION)?;
+        write_u64(writer, clock as u64)?;
+        write_u64(writer, events as u64)?;
+        write_u64(writer, id_generator.current as u64)?;
+        write_u64(writer, pond.width() as u64)?;
+        write_u64(writer, pond.height() as u64)?;
+        for cell in pond.cells() {
+            write_cell(writer, cell)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Checkpoint> {
+        read_header(reader, MAGIC, VERSION)?;
+        let clock = read_u64(reader)? as usize;
+        let events = read_u64(reader)? as usize;
+        let current = read_u64(reader)? as usize;
+        let config = Config {
+            pond_width: read_u64(reader)? as usize,
+            pond_height: read_u64(reader)? as usize,
+            ..Config::default()
+        };
+        config.validate().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let (width, height) = (config.pond_width, config.pond_height);
+        let mut cells = Vec::with_capacity(width * height);
+        for _ in 0..width * height {
+            cells.push(read_cell(reader)?);
+        }
+        Ok(Checkpoint {
+            clock,
+            events,
+            id_generator: CellIdGenerator::starting_at(current),
+            pond: CellPond::from_cells(&config, cells),
+        })
+    }
+}
+
+fn write_cell<W: Write, G: GenomeBuffer>(writer: &mut W, cell: &Cell<G>) -> io::Result<()> {
+    write_u64(writer, cell.id.0 as u64)?;
+    write_u64(writer, cell.parent_id.as_ref().map(|id| id.0 as u64).unwrap_or(0))?;
+    write_u64(writer, cell.lineage.0 as u64)?;
+    write_u64(writer, cell.generation as u64)?;
+    write_u64(writer, cell.energy as u64)?;
+    writer.write_all(&cell.genome().0)
+}
+
+fn read_cell<R: Read>(reader: &mut R) -> io::Result<Cell> {
+    let id = CellId(read_u64(reader)? as usize);
+    let parent_id = match read_u64(reader)? {
+        0 => None,
+        parent => Some(CellId(parent as usize)),
+    };
+    let lineage = CellId(read_u64(reader)? as usize);
+    let generation = read_u64(reader)? as usize;
+    let energy = read_u64(reader)? as usize;
+    let mut genome = [0; GENOME_SIZE];
+    reader.read_exact(&mut genome)?;
+    Ok(Cell {
+        id,
+        parent_id,
+        lineage,
+        generation,
+        energy,
+        genome: Genome(genome),
+    })
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Read, Write};
+
+#[inline]
+pub(crate) fn write_header<W: Write>(writer: &mut W, magic: &[u8; 4], version: u32) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&version.to_le_bytes())
+}
+
+pub(crate) fn read_header<R: Read>(reader: &mut R, magic: &[u8; 4], version: u32) -> io::Result<()> {
+    let mut found = [0; 4];
+    reader.read_exact(&mut found)?;
+    if &found != magic {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Unexpected file type"));
+    }
+    if read_u32(reader)? != version {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Unsupported version"));
+    }
+    Ok(())
+}
+
+#[inline]
+pub(crate) fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+#[inline]
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+#[inline]
+pub(crate) fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(crate) fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+#[inline]
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[inline]
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
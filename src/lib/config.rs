@@ -0,0 +1,118 @@
+use std::fmt;
+use inflow::Inflow;
+use rng::RngKind;
+use schedule::Schedules;
+use topology::{Boundary, Topology};
+use super::{INFLOW_FREQUENCY, INFLOW_RATE_BASE, MUTATION_RATE, POND_HEIGHT, POND_WIDTH, REPORT_FREQUENCY};
+
+/// How the cell executed at every tick is picked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheduling {
+    /// A random position, whether the cell there has energy or not.
+    Uniform,
+    /// A random cell among those with energy, at the ticks where uniform
+    /// scheduling would have picked one. The ticks in between are skipped
+    /// without drawing positions, so a run follows the same process as
+    /// with uniform scheduling but not the same random stream.
+    Active,
+}
+
+impl fmt::Display for Scheduling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Scheduling::Uniform => write!(f, "uniform"),
+            Scheduling::Active => write!(f, "active"),
+        }
+    }
+}
+
+/// Parameters of a run. The defaults are the values the pond always used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub pond_width: usize,
+    pub pond_height: usize,
+    pub mutation_rate: usize,
+    pub inflow_rate_base: usize,
+    pub inflow_frequency: usize,
+    pub report_frequency: usize,
+    pub scheduling: Scheduling,
+    pub rng: RngKind,
+    pub topology: Topology,
+    pub boundary: Boundary,
+    pub inflow: Inflow,
+    /// Drive the inflow rate, inflow frequency and mutation rate, the
+    /// fields above being their base values.
+    pub schedules: Schedules,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            pond_width: POND_WIDTH,
+            pond_height: POND_HEIGHT,
+            mutation_rate: MUTATION_RATE,
+            inflow_rate_base: INFLOW_RATE_BASE,
+            inflow_frequency: INFLOW_FREQUENCY,
+            report_frequency: REPORT_FREQUENCY,
+            scheduling: Scheduling::Uniform,
+            rng: RngKind::Xorshift,
+            topology: Topology::VonNeumann,
+            boundary: Boundary::Torus,
+            inflow: Inflow::Uniform,
+            schedules: Schedules::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Sets a parameter by name. `pond_size` takes `<width>x<height>`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || value.trim().parse::<usize>()
+            .map_err(|_| format!("Invalid value {} for {}", value, key));
+        match key {
+            "pond_width" => self.pond_width = number()?,
+            "pond_height" => self.pond_height = number()?,
+            "pond_size" => {
+                let mut dimensions = value.trim().split('x').map(|n| n.parse::<usize>());
+                match (dimensions.next(), dimensions.next(), dimensions.next()) {
+                    (Some(Ok(width)), Some(Ok(height)), None) => {
+                        self.pond_width = width;
+                        self.pond_height = height;
+                    },
+                    _ => return Err(format!("Invalid value {} for {}", value, key)),
+                }
+            },
+            "mutation_rate" => self.mutation_rate = number()?,
+            "inflow_rate_base" => self.inflow_rate_base = number()?,
+            "inflow_frequency" => self.inflow_frequency = number()?,
+            "report_frequency" => self.report_frequency = number()?,
+            "rng" => self.rng = value.trim().parse()?,
+            "topology" => self.topology = value.trim().parse()?,
+            "boundary" => self.boundary = value.trim().parse()?,
+            "inflow" => self.inflow = value.trim().parse()?,
+            "inflow_rate_schedule" => self.schedules.inflow_rate = Some(value.trim().parse()?),
+            "inflow_frequency_schedule" => self.schedules.inflow_frequency = Some(value.trim().parse()?),
+            "mutation_rate_schedule" => self.schedules.mutation_rate = Some(value.trim().parse()?),
+            "scheduling" => self.scheduling = match value.trim() {
+                "uniform" => Scheduling::Uniform,
+                "active" => Scheduling::Active,
+                _ => return Err(format!("Invalid value {} for {}", value, key)),
+            },
+            _ => return Err(format!("Unknown parameter {}", key)),
+        }
+        self.validate()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pond_width < 2 || self.pond_height < 2 {
+            return Err(format!("The pond must be at least 2x2, got {}x{}", self.pond_width, self.pond_height));
+        }
+        if self.inflow_frequency == 0 || self.report_frequency == 0 {
+            return Err("Frequencies must be positive".to_string());
+        }
+        if self.topology == Topology::Hex && !self.pond_height.is_multiple_of(2) {
+            return Err(format!("A hex pond needs an even height, got {}", self.pond_height));
+        }
+        Ok(())
+    }
+}
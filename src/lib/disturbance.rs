@@ -0,0 +1,419 @@
+use std::fmt;
+use std::io::{self, Write};
+use cell::CellPosition;
+use rng::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Rectangle { width: usize, height: usize },
+    Circle { radius: usize },
+}
+
+/// Where a disturbance strikes: a rectangle from its lower corner or a
+/// circle around its center. A coordinate left out is drawn anew every
+/// time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub shape: Shape,
+    pub x: Option<usize>,
+    pub y: Option<usize>,
+}
+
+impl Region {
+    fn place<R: Rng>(&self, width: usize, height: usize, random: &mut R) -> Area {
+        Area {
+            shape: self.shape,
+            x: self.x.unwrap_or_else(|| random.generate_below(width as u64) as usize) % width,
+            y: self.y.unwrap_or_else(|| random.generate_below(height as u64) as usize) % height,
+        }
+    }
+}
+
+/// A region placed in the pond. It wraps around the edges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Area {
+    shape: Shape,
+    x: usize,
+    y: usize,
+}
+
+impl Area {
+    #[inline]
+    pub(crate) fn contains(&self, position: &CellPosition, width: usize, height: usize) -> bool {
+        let dx = (position.0 + width - self.x) % width;
+        let dy = (position.1 + height - self.y) % height;
+        match self.shape {
+            Shape::Rectangle { width: rectangle_width, height: rectangle_height } =>
+                dx < rectangle_width && dy < rectangle_height,
+            Shape::Circle { radius } => {
+                let (dx, dy) = (dx.min(width - dx), dy.min(height - dy));
+                dx * dx + dy * dy <= radius * radius
+            },
+        }
+    }
+
+    /// The cells of the pond within the area, row by row.
+    pub(crate) fn cells(&self, width: usize, height: usize) -> Vec<CellPosition> {
+        let (columns, rows) = match self.shape {
+            Shape::Rectangle { width: rectangle_width, height: rectangle_height } => (
+                (0..rectangle_width.min(width)).map(|dx| (self.x + dx) % width).collect(),
+                (0..rectangle_height.min(height)).map(|dy| (self.y + dy) % height).collect(),
+            ),
+            Shape::Circle { radius } => (around(self.x, radius, width), around(self.y, radius, height)),
+        };
+        rows.iter()
+            .flat_map(|y| columns.iter().map(move |x| CellPosition(*x, *y)))
+            .filter(|position| self.contains(position, width, height))
+            .collect()
+    }
+}
+
+/// The coordinates within `radius` of `center`, each once.
+fn around(center: usize, radius: usize, length: usize) -> Vec<usize> {
+    if 2 * radius + 1 >= length {
+        (0..length).collect()
+    } else {
+        (0..2 * radius + 1).map(|offset| (center + length - radius + offset) % length).collect()
+    }
+}
+
+impl fmt::Display for Area {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.shape {
+            Shape::Rectangle { width, height } => write!(f, "rect {} {} {} {}", self.x, self.y, width, height),
+            Shape::Circle { radius } => write!(f, "circle {} {} {}", self.x, self.y, radius),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Disturbance {
+    /// Empties the cells of a region: no energy and a blank genome.
+    Wipe(Region),
+    /// Empties every cell with the given probability.
+    Sweep(f64),
+    /// Raises the mutation rate of a region to `rate` for `duration` ticks.
+    MutationBoost { region: Region, rate: usize, duration: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    At(usize),
+    Every(usize),
+    /// At random, the given number of ticks apart on average.
+    Random(usize),
+}
+
+impl Timing {
+    /// The first tick after `tick` this timing strikes at.
+    fn next_tick<R: Rng>(&self, tick: usize, random: &mut R) -> usize {
+        match *self {
+            Timing::At(at) if at > tick => at,
+            Timing::At(_) => usize::MAX,
+            Timing::Every(period) => tick - tick % period + period,
+            Timing::Random(mean) => (tick + 1).saturating_add(random.generate_geometric(1.0 / mean as f64)),
+        }
+    }
+}
+
+/// A disturbance as it struck the pond.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Strike {
+    Wipe(Area),
+    Sweep(f64),
+    MutationBoost { area: Area, rate: usize, until: usize },
+}
+
+impl fmt::Display for Strike {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Strike::Wipe(area) => write!(f, "wipe {}", area),
+            Strike::Sweep(fraction) => write!(f, "sweep {}", fraction),
+            Strike::MutationBoost { area, rate, until } => write!(f, "boost {} {} until {}", area, rate, until),
+        }
+    }
+}
+
+/// A raised mutation rate in an area until some tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MutationBoost {
+    pub(crate) area: Area,
+    pub(crate) rate: usize,
+    pub(crate) until: usize,
+}
+
+/// The mutation rate at `position`, the highest of `base` and the boosts
+/// covering it.
+#[inline]
+pub(crate) fn boosted_mutation_rate(
+    boosts: &[MutationBoost], base: usize, position: &CellPosition, width: usize, height: usize) -> usize {
+    boosts.iter()
+        .filter(|boost| boost.area.contains(position, width, height))
+        .fold(base, |rate, boost| rate.max(boost.rate))
+}
+
+/// The disturbances of a run, read from a file with one per line:
+///
+/// ```text
+/// # <when> <disturbance>
+/// 100000 wipe rect 10 10 50 50
+/// every:500000 wipe circle ? ? 30
+/// random:1000000 sweep 0.5
+/// 200000 boost circle 400 300 50 50000 100000
+/// ```
+///
+/// `<when>` is a tick, `every:<ticks>` or `random:<mean ticks apart>`, a
+/// `?` coordinate is drawn at random, and a boost ends with its rate and
+/// duration. Every strike is logged with its tick and the number of cells
+/// it reached.
+pub struct Disturbances {
+    scheduled: Vec<(Timing, Disturbance)>,
+    /// The next tick of each scheduled disturbance.
+    next: Vec<usize>,
+    log: Option<Box<dyn Write + Send>>,
+}
+
+impl Disturbances {
+    pub fn parse(text: &str) -> Result<Disturbances, String> {
+        let mut scheduled = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid disturbance on line {}: {}", number + 1, line);
+            scheduled.push(parse_line(line).ok_or_else(invalid)?);
+        }
+        Ok(Disturbances { scheduled, next: Vec::new(), log: None })
+    }
+
+    /// Writes every strike to `log` as `<tick>,<strike>,<cells>`.
+    #[inline]
+    pub fn log_to(&mut self, log: Box<dyn Write + Send>) {
+        self.log = Some(log);
+    }
+
+    /// Draws the first tick of every disturbance.
+    pub(crate) fn start<R: Rng>(&mut self, clock: usize, random: &mut R) {
+        self.next = self.scheduled.iter()
+            .map(|(timing, _)| timing.next_tick(clock, random))
+            .collect();
+    }
+
+    /// The tick of the next strike.
+    #[inline]
+    pub(crate) fn next_strike(&self) -> usize {
+        self.next.iter().cloned().min().unwrap_or(usize::MAX)
+    }
+
+    /// The disturbances striking at `clock`, placed in a `width` by
+    /// `height` pond, their next ticks drawn.
+    pub(crate) fn strike<R: Rng>(
+        &mut self, clock: usize, width: usize, height: usize, random: &mut R) -> Vec<Strike> {
+        let mut strikes = Vec::new();
+        for ((timing, disturbance), next) in self.scheduled.iter().zip(self.next.iter_mut()) {
+            if *next > clock {
+                continue;
+            }
+            strikes.push(match *disturbance {
+                Disturbance::Wipe(region) => Strike::Wipe(region.place(width, height, random)),
+                Disturbance::Sweep(fraction) => Strike::Sweep(fraction),
+                Disturbance::MutationBoost { region, rate, duration } => Strike::MutationBoost {
+                    area: region.place(width, height, random),
+                    rate,
+                    until: clock + duration,
+                },
+            });
+            *next = timing.next_tick(clock, random);
+        }
+        strikes
+    }
+
+    pub(crate) fn log(&mut self, clock: usize, strike: &Strike, cells: usize) -> io::Result<()> {
+        match self.log {
+            Some(ref mut log) => {
+                writeln!(log, "{},{},{}", clock, strike, cells)?;
+                log.flush()
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Timing, Disturbance)> {
+    let mut fields = line.split_whitespace();
+    let when = fields.next()?;
+    let timing = match when.split_once(':') {
+        None => Timing::At(when.parse().ok()?),
+        Some(("every", period)) => Timing::Every(period.parse().ok().filter(|period| *period > 0)?),
+        Some(("random", mean)) => Timing::Random(mean.parse().ok().filter(|mean| *mean > 0)?),
+        Some(_) => return None,
+    };
+    let disturbance = match fields.next()? {
+        "wipe" => Disturbance::Wipe(parse_region(&mut fields)?),
+        "sweep" => Disturbance::Sweep(fields.next()?.parse().ok().filter(|f| (0.0..=1.0).contains(f))?),
+        "boost" => Disturbance::MutationBoost {
+            region: parse_region(&mut fields)?,
+            rate: fields.next()?.parse().ok()?,
+            duration: fields.next()?.parse().ok()?,
+        },
+        _ => return None,
+    };
+    match fields.next() {
+        Some(_) => None,
+        None => Some((timing, disturbance)),
+    }
+}
+
+fn parse_region<'a, I: Iterator<Item=&'a str>>(fields: &mut I) -> Option<Region> {
+    let kind = fields.next()?;
+    let mut coordinate = || match fields.next()? {
+        "?" => Some(None),
+        x => x.parse().ok().map(Some),
+    };
+    let (x, y) = (coordinate()?, coordinate()?);
+    let mut size = || fields.next()?.parse::<usize>().ok();
+    let shape = match kind {
+        "rect" => Shape::Rectangle { width: size()?, height: size()? },
+        "circle" => Shape::Circle { radius: size()? },
+        _ => return None,
+    };
+    Some(Region { shape, x, y })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::SplitMix64;
+
+    const WIDTH: usize = 7;
+    const HEIGHT: usize = 5;
+
+    fn coordinates(cells: &[CellPosition]) -> Vec<(usize, usize)> {
+        cells.iter().map(|position| (position.0, position.1)).collect()
+    }
+
+    fn rectangle(x: usize, y: usize, width: usize, height: usize) -> Area {
+        Area { shape: Shape::Rectangle { width, height }, x, y }
+    }
+
+    fn circle(x: usize, y: usize, radius: usize) -> Area {
+        Area { shape: Shape::Circle { radius }, x, y }
+    }
+
+    #[test]
+    fn parses_every_kind_of_line() {
+        let region = |shape, x, y| Region { shape, x, y };
+        assert_eq!(parse_line("100000 wipe rect 10 10 50 50"), Some((
+            Timing::At(100000),
+            Disturbance::Wipe(region(Shape::Rectangle { width: 50, height: 50 }, Some(10), Some(10))))));
+        assert_eq!(parse_line("every:500000 wipe circle ? ? 30"), Some((
+            Timing::Every(500000),
+            Disturbance::Wipe(region(Shape::Circle { radius: 30 }, None, None)))));
+        assert_eq!(parse_line("random:1000000 sweep 0.5"), Some((Timing::Random(1000000), Disturbance::Sweep(0.5))));
+        assert_eq!(parse_line("200000 boost circle 400 ? 50 50000 100000"), Some((
+            Timing::At(200000),
+            Disturbance::MutationBoost {
+                region: region(Shape::Circle { radius: 50 }, Some(400), None),
+                rate: 50000,
+                duration: 100000,
+            })));
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for line in ["every:0 sweep 0.5", "random:0 sweep 0.5", "later:5 sweep 0.5", "5 sweep 1.5",
+                     "5 wipe rect 1 2 3", "5 wipe square 1 2 3", "5 wipe circle ? 3", "5 sweep 0.5 0.5",
+                     "5 boost circle 1 2 3 4", "5 explode", "5"].iter() {
+            assert_eq!(parse_line(line), None, "{}", line);
+        }
+        assert_eq!(Disturbances::parse("# when what\n\n5 sweep 0.5\n5 explode").err(),
+                   Some("Invalid disturbance on line 4: 5 explode".to_string()));
+    }
+
+    #[test]
+    fn rectangles_wrap_around_the_edges() {
+        assert_eq!(coordinates(&rectangle(6, 4, 3, 2).cells(WIDTH, HEIGHT)),
+                   vec![(6, 4), (0, 4), (1, 4), (6, 0), (0, 0), (1, 0)]);
+        assert_eq!(rectangle(3, 2, 10, 10).cells(WIDTH, HEIGHT).len(), WIDTH * HEIGHT);
+    }
+
+    #[test]
+    fn circles_wrap_around_the_edges() {
+        assert_eq!(coordinates(&circle(0, 0, 1).cells(WIDTH, HEIGHT)),
+                   vec![(0, 4), (6, 0), (0, 0), (1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn circles_as_wide_as_the_pond_reach_each_cell_once() {
+        assert_eq!(around(2, 2, 5), vec![0, 1, 2, 3, 4]);
+        assert_eq!(around(0, 3, 5), vec![0, 1, 2, 3, 4]);
+        assert_eq!(around(0, 2, 6), vec![4, 5, 0, 1, 2]);
+        // Its 5 rows span the pond's height, 5 + 2 * 3 + 2 * 1 cells.
+        assert_eq!(circle(3, 2, 2).cells(WIDTH, HEIGHT).len(), 13);
+        assert_eq!(circle(3, 2, 10).cells(WIDTH, HEIGHT).len(), WIDTH * HEIGHT);
+    }
+
+    #[test]
+    fn cells_agree_with_contains() {
+        let mut areas = Vec::new();
+        for (x, y) in [(0, 0), (6, 4), (0, 4), (3, 2)].iter() {
+            for radius in 0..5 {
+                areas.push(circle(*x, *y, radius));
+            }
+            for (width, height) in [(1, 1), (2, 3), (7, 1), (8, 6)].iter() {
+                areas.push(rectangle(*x, *y, *width, *height));
+            }
+        }
+        for area in areas {
+            let mut cells = coordinates(&area.cells(WIDTH, HEIGHT));
+            cells.sort_unstable();
+            cells.dedup();
+            let contained: Vec<(usize, usize)> = (0..WIDTH)
+                .flat_map(|x| (0..HEIGHT).map(move |y| (x, y)))
+                .filter(|(x, y)| area.contains(&CellPosition(*x, *y), WIDTH, HEIGHT))
+                .collect();
+            assert_eq!(cells.len(), area.cells(WIDTH, HEIGHT).len(), "{} lists a cell twice", area);
+            assert_eq!(cells, contained, "{}", area);
+        }
+    }
+
+    #[test]
+    fn regions_are_placed_within_the_pond() {
+        let mut random = SplitMix64::new(1);
+        let fixed = Region { shape: Shape::Circle { radius: 1 }, x: Some(9), y: Some(4) };
+        assert_eq!(fixed.place(WIDTH, HEIGHT, &mut random), circle(2, 4, 1));
+        let drawn = Region { shape: Shape::Circle { radius: 1 }, x: None, y: None };
+        for _ in 0..100 {
+            let area = drawn.place(WIDTH, HEIGHT, &mut random);
+            assert!(area.x < WIDTH && area.y < HEIGHT);
+        }
+    }
+
+    #[test]
+    fn timings_strike_after_the_tick() {
+        let mut random = SplitMix64::new(1);
+        let mut next = |timing: Timing, tick| timing.next_tick(tick, &mut random);
+        assert_eq!(next(Timing::At(100), 0), 100);
+        assert_eq!(next(Timing::At(100), 99), 100);
+        assert_eq!(next(Timing::At(100), 100), usize::MAX);
+        assert_eq!(next(Timing::Every(50), 0), 50);
+        assert_eq!(next(Timing::Every(50), 49), 50);
+        assert_eq!(next(Timing::Every(50), 50), 100);
+        assert_eq!(next(Timing::Every(50), 120), 150);
+        assert_eq!(next(Timing::Random(1), 7), 8);
+    }
+
+    #[test]
+    fn random_timings_are_their_mean_apart() {
+        let mut random = SplitMix64::new(1);
+        let draws = 10000;
+        let mut tick = 0;
+        for _ in 0..draws {
+            let next = Timing::Random(100).next_tick(tick, &mut random);
+            assert!(next > tick);
+            tick = next;
+        }
+        assert!((tick as f64 / draws as f64 - 100.0).abs() < 5.0, "{}", tick / draws);
+    }
+}
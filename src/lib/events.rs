@@ -0,0 +1,255 @@
+use std::io::{self, Read, Write};
+use cell::{Cell, CellId, CellPosition};
+use codec::{read_header, read_u16, read_u64, read_u8, write_header, write_u16, write_u64, write_u8};
+use genome_store::GenomeBuffer;
+
+const MAGIC: &[u8; 4] = b"RPEV";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    /// A random genome flowing into the pond.
+    Inflow,
+    /// Everything a cell execution changed: its own energy use, mutations
+    /// and genome writes, and the reproductions, kills and shares it made.
+    Execution,
+    /// The cells a disturbance emptied.
+    Disturbance,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellHeader {
+    pub id: CellId,
+    pub parent_id: Option<CellId>,
+    pub lineage: CellId,
+    pub generation: usize,
+    pub energy: usize,
+}
+
+impl<'a, G: GenomeBuffer> From<&'a Cell<G>> for CellHeader {
+    fn from(cell: &'a Cell<G>) -> CellHeader {
+        CellHeader {
+            id: cell.id.clone(),
+            parent_id: cell.parent_id.clone(),
+            lineage: cell.lineage.clone(),
+            generation: cell.generation,
+            energy: cell.energy,
+        }
+    }
+}
+
+/// The change of a single cell, reversible since it holds the state before
+/// and after. Only the genome bytes that changed are kept.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub tick: usize,
+    pub kind: EventKind,
+    pub x: usize,
+    pub y: usize,
+    pub before: CellHeader,
+    pub after: CellHeader,
+    pub genome: Vec<(u16, u8, u8)>,
+}
+
+impl Event {
+    fn new<G: GenomeBuffer>(tick: usize, kind: EventKind, position: &CellPosition, before: &Cell<G>, after: &Cell<G>)
+        -> Option<Event> {
+        let genome: Vec<(u16, u8, u8)> = before.genome().0.iter()
+            .zip(after.genome().0.iter())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(index, (before, after))| (index as u16, *before, *after))
+            .collect();
+        let before = CellHeader::from(before);
+        let after = CellHeader::from(after);
+        if before == after && genome.is_empty() {
+            None
+        } else {
+            Some(Event { tick, kind, x: position.0, y: position.1, before, after, genome })
+        }
+    }
+
+    #[inline]
+    pub(crate) fn apply<G: GenomeBuffer>(&self, cell: &mut Cell<G>) {
+        set_header(cell, &self.after);
+        for (index, _, after) in self.genome.iter() {
+            cell.genome_mut().0[*index as usize] = *after;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn revert<G: GenomeBuffer>(&self, cell: &mut Cell<G>) {
+        set_header(cell, &self.before);
+        for (index, before, _) in self.genome.iter() {
+            cell.genome_mut().0[*index as usize] = *before;
+        }
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_u64(writer, self.tick as u64)?;
+        write_u8(writer, match self.kind {
+            EventKind::Inflow => 0,
+            EventKind::Execution => 1,
+            EventKind::Disturbance => 2,
+        })?;
+        write_u64(writer, self.x as u64)?;
+        write_u64(writer, self.y as u64)?;
+        write_header_fields(writer, &self.before)?;
+        write_header_fields(writer, &self.after)?;
+        write_u16(writer, self.genome.len() as u16)?;
+        for (index, before, after) in self.genome.iter() {
+            write_u16(writer, *index)?;
+            write_u8(writer, *before)?;
+            write_u8(writer, *after)?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R) -> io::Result<Option<Event>> {
+        let mut tick = [0; 8];
+        match reader.read_exact(&mut tick) {
+            Ok(()) => {},
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        let kind = match read_u8(reader)? {
+            0 => EventKind::Inflow,
+            1 => EventKind::Execution,
+            2 => EventKind::Disturbance,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown event kind")),
+        };
+        let x = read_u64(reader)? as usize;
+        let y = read_u64(reader)? as usize;
+        let before = read_header_fields(reader)?;
+        let after = read_header_fields(reader)?;
+        let changes = read_u16(reader)?;
+        let mut genome = Vec::with_capacity(changes as usize);
+        for _ in 0..changes {
+            genome.push((read_u16(reader)?, read_u8(reader)?, read_u8(reader)?));
+        }
+        Ok(Some(Event {
+            tick: u64::from_le_bytes(tick) as usize,
+            kind,
+            x,
+            y,
+            before,
+            after,
+            genome,
+        }))
+    }
+}
+
+#[inline]
+fn set_header<G: GenomeBuffer>(cell: &mut Cell<G>, header: &CellHeader) {
+    cell.id = header.id.clone();
+    cell.parent_id = header.parent_id.clone();
+    cell.lineage = header.lineage.clone();
+    cell.generation = header.generation;
+    cell.energy = header.energy;
+}
+
+fn write_header_fields<W: Write>(writer: &mut W, header: &CellHeader) -> io::Result<()> {
+    write_u64(writer, header.id.0 as u64)?;
+    write_u64(writer, header.parent_id.as_ref().map(|id| id.0 as u64).unwrap_or(0))?;
+    write_u64(writer, header.lineage.0 as u64)?;
+    write_u64(writer, header.generation as u64)?;
+    write_u64(writer, header.energy as u64)
+}
+
+fn read_header_fields<R: Read>(reader: &mut R) -> io::Result<CellHeader> {
+    let id = CellId(read_u64(reader)? as usize);
+    let parent_id = match read_u64(reader)? {
+        0 => None,
+        parent => Some(CellId(parent as usize)),
+    };
+    Ok(CellHeader {
+        id,
+        parent_id,
+        lineage: CellId(read_u64(reader)? as usize),
+        generation: read_u64(reader)? as usize,
+        energy: read_u64(reader)? as usize,
+    })
+}
+
+/// Stream of events in the order they happened. The file holds the `RPEV`
+/// magic and a little endian u32 version followed by the events.
+pub struct EventLog {
+    writer: Box<dyn Write + Send>,
+}
+
+impl EventLog {
+    pub fn new(mut writer: Box<dyn Write + Send>) -> io::Result<EventLog> {
+        write_header(&mut writer, MAGIC, VERSION)?;
+        Ok(EventLog { writer })
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Vec<Event>> {
+        read_header(reader, MAGIC, VERSION)?;
+        let mut events = Vec::new();
+        while let Some(event) = Event::read(reader)? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Keeps the state of every cell handed out for modification since the
+/// last commit, so the changes can be turned into events.
+pub(crate) struct Journal<G: GenomeBuffer> {
+    log: EventLog,
+    touched: Vec<(CellPosition, Cell<G>)>,
+    /// The commit each cell was last touched in, so that it is kept once.
+    touches: Vec<usize>,
+    commits: usize,
+    written: usize,
+}
+
+impl<G: GenomeBuffer> Journal<G> {
+    /// A journal of a pond of `cells` cells.
+    pub(crate) fn new(log: EventLog, cells: usize) -> Journal<G> {
+        Journal {
+            log,
+            touched: Vec::with_capacity(4),
+            touches: vec![0; cells],
+            commits: 1,
+            written: 0,
+        }
+    }
+
+    /// Keeps the state of the cell at `index` before its first change
+    /// since the last commit.
+    #[inline]
+    pub(crate) fn touch(&mut self, index: usize, position: &CellPosition, cell: &Cell<G>) {
+        if self.touches[index] != self.commits {
+            self.touches[index] = self.commits;
+            self.touched.push((position.clone(), cell.clone()));
+        }
+    }
+
+    pub(crate) fn commit<'a, F: Fn(&CellPosition) -> &'a Cell<G>>(
+        &mut self, tick: usize, kind: EventKind, current: F) -> io::Result<()> {
+        for (position, before) in self.touched.drain(..) {
+            if let Some(event) = Event::new(tick, kind, &position, &before, current(&position)) {
+                event.write(&mut self.log.writer)?;
+                self.written += 1;
+            }
+        }
+        self.commits += 1;
+        Ok(())
+    }
+
+    /// Number of events written to the log so far.
+    #[inline]
+    pub(crate) fn written(&self) -> usize {
+        self.written
+    }
+
+    #[inline]
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.log.flush()
+    }
+}
@@ -1,29 +1,62 @@
-use super::{GENOME_SIZE, RandomGenerator};
+use std::fmt;
+use std::str::FromStr;
+use instruction::Instruction;
+use rng::Rng;
+use super::GENOME_SIZE;
 
 #[derive(Clone)]
 pub struct Genome(pub(crate) [u8; GENOME_SIZE]);
 
+impl Default for Genome {
+    fn default() -> Genome {
+        Genome::new()
+    }
+}
+
 impl Genome {
     #[inline]
     pub fn new() -> Genome {
         Genome([!0; GENOME_SIZE])
     }
     #[inline]
-    pub fn random(generator: &mut RandomGenerator) -> Genome {
+    pub fn random<R: Rng>(generator: &mut R) -> Genome {
         let mut genome = [0; GENOME_SIZE];
-        for i in 0..GENOME_SIZE {
-            let mut n = generator.generate_integer() as u8;
-            genome[i] = n;
+        for byte in genome.iter_mut() {
+            *byte = generator.generate_integer() as u8;
         }
         Genome(genome)
     }
 
+    #[inline]
+    pub fn instructions<'a>(&'a self) -> impl Iterator<Item=Instruction> + 'a {
+        (0..GENOME_SIZE * 2)
+            .map(move |i| Instruction::from(self.get(&GenomePointer::new(i / 2, i % 2 == 0))))
+    }
+
+    /// FNV-1a hash of the genome bytes, stable across runs and platforms.
+    #[inline]
+    pub fn fingerprint(&self) -> u64 {
+        self.0.iter().fold(0xcbf29ce484222325, |hash, byte|
+            (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3))
+    }
+
+    #[inline]
+    pub fn hamming_distance(&self, other: &Genome) -> usize {
+        self.0.iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| {
+                let diff = a ^ b;
+                (diff & 0x0f != 0) as usize + (diff & 0xf0 != 0) as usize
+            })
+            .sum()
+    }
+
     #[inline]
     pub(crate) fn get(&self, pointer: &GenomePointer) -> u8 {
         if pointer.is_lower_byte {
-            (self.0[pointer.array_pointer] & 0xf) as u8
+            self.0[pointer.array_pointer] & 0xf
         } else {
-            ((self.0[pointer.array_pointer] >> 4) & 0xf) as u8
+            (self.0[pointer.array_pointer] >> 4) & 0xf
         }
     }
 
@@ -39,6 +72,34 @@ impl Genome {
     }
 }
 
+impl fmt::Display for Genome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Genome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Genome, String> {
+        let digits: Vec<u8> = s.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(format!("Invalid hex digit {}", c)))
+            .collect::<Result<_, _>>()?;
+        if digits.len() != GENOME_SIZE * 2 {
+            return Err(format!("Expected {} hex digits, got {}", GENOME_SIZE * 2, digits.len()));
+        }
+        let mut genome = [0; GENOME_SIZE];
+        for (byte, pair) in genome.iter_mut().zip(digits.chunks(2)) {
+            *byte = (pair[0] << 4) | pair[1];
+        }
+        Ok(Genome(genome))
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct GenomePointer {
     pub(crate) array_pointer: usize,
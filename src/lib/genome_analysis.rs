@@ -0,0 +1,240 @@
+use std::fmt;
+use cell::Cell;
+use genome::Genome;
+use genome_store::GenomeBuffer;
+use instruction::Instruction;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EcologicalClass {
+    Inert,
+    Predator,
+    Cooperator,
+    Replicator,
+    Parasite,
+}
+
+impl EcologicalClass {
+    pub fn iterator() -> ::std::slice::Iter<'static, EcologicalClass> {
+        static CLASSES: [EcologicalClass; 5] = [
+            EcologicalClass::Inert,
+            EcologicalClass::Predator,
+            EcologicalClass::Cooperator,
+            EcologicalClass::Replicator,
+            EcologicalClass::Parasite,
+        ];
+        CLASSES.iter()
+    }
+
+    #[inline]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for EcologicalClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let name = match self {
+            EcologicalClass::Inert => "inert",
+            EcologicalClass::Predator => "predator",
+            EcologicalClass::Cooperator => "cooperator",
+            EcologicalClass::Replicator => "replicator",
+            EcologicalClass::Parasite => "parasite",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Facts about a genome derived without running it. Only the instructions
+/// up to the first reachable STOP are taken into account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenomeAnalysis {
+    pub effective_length: usize,
+    pub loops: usize,
+    pub reps: usize,
+    pub max_loop_depth: usize,
+    pub copy_loop: bool,
+    pub write_buffers: usize,
+    pub kills: usize,
+    pub shares: usize,
+    pub turns: usize,
+    pub turns_before_interaction: bool,
+}
+
+impl GenomeAnalysis {
+    /// Walks the genome as the VM would from a fresh start, tracking the
+    /// register while it holds a known constant. A LOOP entered with a known
+    /// zero register skips its body, so a STOP in there is not reachable.
+    pub fn new(genome: &Genome) -> GenomeAnalysis {
+        let instructions: Vec<Instruction> = genome.instructions().collect();
+        let mut analysis = GenomeAnalysis {
+            effective_length: instructions.len(),
+            loops: 0,
+            reps: 0,
+            max_loop_depth: 0,
+            copy_loop: false,
+            write_buffers: 0,
+            kills: 0,
+            shares: 0,
+            turns: 0,
+            turns_before_interaction: false,
+        };
+        let mut register = Some(0u8);
+        let mut skip_depth = 0;
+        let mut depth = 0;
+        let mut reads_in_loop = false;
+        let mut writes_in_loop = false;
+        let mut position = 0;
+        while position < instructions.len() {
+            let instruction = instructions[position];
+            position += 1;
+            if skip_depth > 0 {
+                match instruction {
+                    Instruction::Loop => skip_depth += 1,
+                    Instruction::Rep => skip_depth -= 1,
+                    _ => {},
+                }
+                continue;
+            }
+            match instruction {
+                Instruction::Zero => register = Some(0),
+                Instruction::Inc => register = register.map(|r| (r + 1) & 0x0f),
+                Instruction::Dec => register = register.map(|r| r.wrapping_sub(1) & 0x0f),
+                Instruction::ReadGenome => {
+                    register = None;
+                    reads_in_loop |= depth > 0;
+                },
+                Instruction::ReadBuffer => register = None,
+                Instruction::WriteGenome => {},
+                Instruction::WriteBuffer => {
+                    analysis.write_buffers += 1;
+                    writes_in_loop |= depth > 0;
+                },
+                Instruction::Xchg => {
+                    register = None;
+                    position += 1;
+                },
+                Instruction::Loop => {
+                    analysis.loops += 1;
+                    if register == Some(0) {
+                        skip_depth = 1;
+                    } else {
+                        depth += 1;
+                        if depth > analysis.max_loop_depth {
+                            analysis.max_loop_depth = depth;
+                        }
+                    }
+                },
+                Instruction::Rep => {
+                    analysis.reps += 1;
+                    if depth > 0 {
+                        depth -= 1;
+                        if depth == 0 {
+                            analysis.copy_loop |= reads_in_loop && writes_in_loop;
+                            reads_in_loop = false;
+                            writes_in_loop = false;
+                        }
+                    }
+                },
+                Instruction::Turn => analysis.turns += 1,
+                Instruction::Kill | Instruction::Share => {
+                    if instruction == Instruction::Kill {
+                        analysis.kills += 1;
+                    } else {
+                        analysis.shares += 1;
+                    }
+                    if analysis.kills + analysis.shares == 1 {
+                        analysis.turns_before_interaction = analysis.turns > 0;
+                    }
+                },
+                Instruction::Stop => {
+                    analysis.effective_length = position;
+                    break;
+                },
+                Instruction::Fwd | Instruction::Back => {},
+            }
+        }
+        if depth > 0 {
+            analysis.copy_loop |= reads_in_loop && writes_in_loop;
+        }
+        analysis
+    }
+
+    /// A LOOP without its REP (or the other way around) makes the control
+    /// flow depend on whatever is left on the VM's loop stack.
+    #[inline]
+    pub fn balanced_loops(&self) -> bool {
+        self.loops == self.reps
+    }
+
+    /// Coarse classification. Without a WRITEBUFFER a cell can never produce
+    /// offspring. Interactions take precedence over the copy machinery since
+    /// they define how the cell affects its neighbours.
+    pub fn class(&self) -> EcologicalClass {
+        if self.write_buffers == 0 {
+            EcologicalClass::Inert
+        } else if self.kills > 0 {
+            EcologicalClass::Predator
+        } else if self.shares > 0 {
+            EcologicalClass::Cooperator
+        } else if self.copy_loop {
+            EcologicalClass::Replicator
+        } else {
+            EcologicalClass::Parasite
+        }
+    }
+}
+
+pub struct ClassCensus {
+    counts: [usize; 5],
+    total: usize,
+}
+
+impl ClassCensus {
+    pub fn new() -> ClassCensus {
+        ClassCensus {
+            counts: [0; 5],
+            total: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn add<G: GenomeBuffer>(&mut self, cell: &Cell<G>) {
+        let class = GenomeAnalysis::new(cell.genome()).class();
+        self.counts[class.index()] += 1;
+        self.total += 1;
+    }
+
+    #[inline]
+    pub fn count(&self, class: EcologicalClass) -> usize {
+        self.counts[class.index()]
+    }
+
+    #[inline]
+    pub fn fraction(&self, class: EcologicalClass) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.count(class) as f64 / self.total as f64
+        }
+    }
+}
+
+impl Default for ClassCensus {
+    fn default() -> ClassCensus {
+        ClassCensus::new()
+    }
+}
+
+impl fmt::Display for ClassCensus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut first = true;
+        for class in EcologicalClass::iterator() {
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            write!(f, "{:04}", self.fraction(*class))?;
+        }
+        Ok(())
+    }
+}
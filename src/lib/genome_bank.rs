@@ -0,0 +1,146 @@
+use genome::{Genome, GenomePointer};
+use inflow::CumulativeWeights;
+use rng::Rng;
+use super::GENOME_SIZE;
+
+/// Genomes for the inflow, each drawn in proportion to its weight, read
+/// from a file with one per line:
+///
+/// ```text
+/// # <weight> <genome in hex>
+/// 3 0a1b2c...
+/// 1 ffe0d4...
+/// ```
+///
+/// A line with a genome alone weighs 1. The inflow can mutate the copies it
+/// draws and mix them with random genomes, see `set_mutation` and
+/// `set_share`.
+pub struct GenomeBank {
+    genomes: Vec<Genome>,
+    weights: CumulativeWeights,
+    mutation: f64,
+    share: f64,
+}
+
+impl GenomeBank {
+    pub fn parse(text: &str) -> Result<GenomeBank, String> {
+        let mut genomes = Vec::new();
+        let mut weights = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |error: String| format!("Invalid genome on line {}: {}", number + 1, error);
+            let (weight, genome) = match line.split_once(char::is_whitespace) {
+                Some((weight, genome)) => (weight.parse::<f64>().map_err(|_| invalid(weight.to_string()))?, genome),
+                None => (1.0, line),
+            };
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(invalid(format!("weight {}", weight)));
+            }
+            genomes.push(genome.parse().map_err(invalid)?);
+            weights.push(weight);
+        }
+        let weights = CumulativeWeights::new(weights).ok_or_else(|| "No genome has a weight".to_string())?;
+        Ok(GenomeBank { genomes, weights, mutation: 0.0, share: 1.0 })
+    }
+
+    /// Replaces every instruction of a copy drawn from the bank by a random
+    /// one with probability `mutation`.
+    pub fn set_mutation(&mut self, mutation: f64) -> Result<(), String> {
+        self.mutation = probability(mutation)?;
+        Ok(())
+    }
+
+    /// Draws a genome from the bank with probability `share` and a random
+    /// one otherwise.
+    pub fn set_share(&mut self, share: f64) -> Result<(), String> {
+        self.share = probability(share)?;
+        Ok(())
+    }
+
+    pub(crate) fn draw<R: Rng>(&self, random: &mut R) -> Genome {
+        if self.share < 1.0 && random.generate_float() >= self.share {
+            return Genome::random(random);
+        }
+        let mut genome = self.genomes[self.weights.pick(random)].clone();
+        if self.mutation > 0.0 {
+            let mut instruction = random.generate_geometric(self.mutation);
+            while instruction < GENOME_SIZE * 2 {
+                let pointer = GenomePointer::new(instruction / 2, instruction.is_multiple_of(2));
+                genome.set(&pointer, random.generate_below(16) as u8);
+                instruction = instruction.saturating_add(random.generate_geometric(self.mutation)).saturating_add(1);
+            }
+        }
+        genome
+    }
+}
+
+fn probability(value: f64) -> Result<f64, String> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{} isn't a probability", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rng::SplitMix64;
+
+    fn genome(digit: char) -> String {
+        digit.to_string().repeat(GENOME_SIZE * 2)
+    }
+
+    /// How many of `draws` draws from `bank` start with each byte.
+    fn counts(bank: &GenomeBank, draws: usize) -> Vec<(u8, usize)> {
+        let mut random = SplitMix64::new(1);
+        let mut counts: Vec<(u8, usize)> = Vec::new();
+        for _ in 0..draws {
+            let first = bank.draw(&mut random).0[0];
+            match counts.iter_mut().find(|(byte, _)| *byte == first) {
+                Some(count) => count.1 += 1,
+                None => counts.push((first, 1)),
+            }
+        }
+        counts.sort_unstable();
+        counts
+    }
+
+    #[test]
+    fn parses_weights_and_genomes_alone() {
+        let text = format!("# weight genome\n\n2 {}\n{}\n0 {}\n", genome('a'), genome('b'), genome('c'));
+        let bank = GenomeBank::parse(&text).unwrap();
+        assert_eq!(bank.genomes.len(), 3);
+        // The genome alone weighs 1 against 2, the one weighing 0 is never
+        // drawn.
+        let counts = counts(&bank, 3000);
+        assert_eq!(counts.iter().map(|(byte, _)| *byte).collect::<Vec<_>>(), vec![0xaa, 0xbb]);
+        assert!((counts[0].1 as f64 / 3000.0 - 2.0 / 3.0).abs() < 0.03, "{:?}", counts);
+    }
+
+    #[test]
+    fn rejects_negative_and_missing_weights() {
+        assert_eq!(GenomeBank::parse(&format!("1 {}\n-1 {}", genome('a'), genome('b'))).err(),
+                   Some("Invalid genome on line 2: weight -1".to_string()));
+        assert_eq!(GenomeBank::parse(&format!("inf {}", genome('a'))).err(),
+                   Some("Invalid genome on line 1: weight inf".to_string()));
+        assert_eq!(GenomeBank::parse(&format!("x {}", genome('a'))).err(),
+                   Some("Invalid genome on line 1: x".to_string()));
+        assert_eq!(GenomeBank::parse(&format!("0 {}\n0 {}", genome('a'), genome('b'))).err(),
+                   Some("No genome has a weight".to_string()));
+        assert_eq!(GenomeBank::parse("# empty").err(), Some("No genome has a weight".to_string()));
+        assert!(GenomeBank::parse("1 abc").is_err());
+    }
+
+    #[test]
+    fn mixes_in_random_genomes() {
+        let mut bank = GenomeBank::parse(&genome('a')).unwrap();
+        assert!(bank.set_share(1.5).is_err());
+        bank.set_share(0.5).unwrap();
+        let banked = counts(&bank, 2000).iter().find(|(byte, _)| *byte == 0xaa).map_or(0, |(_, count)| *count);
+        assert!((banked as f64 / 2000.0 - 0.5).abs() < 0.05, "{}", banked);
+    }
+}
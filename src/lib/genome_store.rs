@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use genome::Genome;
+
+const MIN_PRUNE_SIZE: usize = 1024;
+
+/// How a cell holds its genome: `Genome` keeps it inline, while
+/// `Arc<Genome>` lets cells born with the same genome share one buffer
+/// from a `GenomeStore`.
+pub trait GenomeBuffer: Clone + Send + Sync + 'static {
+    fn new(genome: Genome) -> Self;
+
+    fn genome(&self) -> &Genome;
+
+    /// The genome for a write, copied first if it is shared.
+    fn genome_mut(&mut self) -> &mut Genome;
+
+    /// The buffer `store` hands out for `genome`.
+    fn intern(store: &mut GenomeStore, genome: &Genome) -> Self;
+}
+
+impl GenomeBuffer for Genome {
+    #[inline]
+    fn new(genome: Genome) -> Genome {
+        genome
+    }
+
+    #[inline]
+    fn genome(&self) -> &Genome {
+        self
+    }
+
+    #[inline]
+    fn genome_mut(&mut self) -> &mut Genome {
+        self
+    }
+
+    #[inline]
+    fn intern(_store: &mut GenomeStore, genome: &Genome) -> Genome {
+        genome.clone()
+    }
+}
+
+impl GenomeBuffer for Arc<Genome> {
+    #[inline]
+    fn new(genome: Genome) -> Arc<Genome> {
+        Arc::new(genome)
+    }
+
+    #[inline]
+    fn genome(&self) -> &Genome {
+        self
+    }
+
+    #[inline]
+    fn genome_mut(&mut self) -> &mut Genome {
+        Arc::make_mut(self)
+    }
+
+    #[inline]
+    fn intern(store: &mut GenomeStore, genome: &Genome) -> Arc<Genome> {
+        store.intern(genome)
+    }
+}
+
+/// Hash-consing store handing out a single shared buffer for every
+/// distinct genome. Cells write to their genome through `genome_mut`,
+/// which gives them a private copy first, so sharing never changes what a
+/// cell sees.
+pub struct GenomeStore {
+    genomes: HashMap<u64, Vec<Arc<Genome>>>,
+    len: usize,
+    prune_at: usize,
+}
+
+impl Default for GenomeStore {
+    fn default() -> GenomeStore {
+        GenomeStore::new()
+    }
+}
+
+impl GenomeStore {
+    pub fn new() -> GenomeStore {
+        GenomeStore {
+            genomes: HashMap::new(),
+            len: 0,
+            prune_at: MIN_PRUNE_SIZE,
+        }
+    }
+
+    pub fn intern(&mut self, genome: &Genome) -> Arc<Genome> {
+        let bucket = self.genomes.entry(genome.fingerprint()).or_default();
+        if let Some(shared) = bucket.iter().find(|shared| shared.0 == genome.0) {
+            return shared.clone();
+        }
+        let shared = Arc::new(genome.clone());
+        bucket.push(shared.clone());
+        self.len += 1;
+        if self.len >= self.prune_at {
+            self.prune();
+        }
+        shared
+    }
+
+    /// Number of distinct genomes in the store.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drops the genomes no cell uses any more.
+    pub(crate) fn prune(&mut self) {
+        self.genomes.retain(|_, bucket| {
+            bucket.retain(|shared| Arc::strong_count(shared) > 1);
+            !bucket.is_empty()
+        });
+        self.len = self.genomes.values().map(|bucket| bucket.len()).sum();
+        self.prune_at = (self.len * 2).max(MIN_PRUNE_SIZE);
+    }
+}
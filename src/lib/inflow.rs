@@ -0,0 +1,174 @@
+use std::fmt;
+use std::str::FromStr;
+use cell::CellPosition;
+use mask::Mask;
+use rng::Rng;
+
+/// Picks where the genomes flowing into the pond land.
+pub trait InflowStrategy: Send {
+    fn position(&self, random: &mut dyn Rng) -> CellPosition;
+}
+
+/// Every cell alike, as the pond always did.
+pub struct Uniform {
+    width: usize,
+    height: usize,
+}
+
+impl Uniform {
+    #[inline]
+    pub fn new(width: usize, height: usize) -> Uniform {
+        Uniform { width, height }
+    }
+}
+
+impl InflowStrategy for Uniform {
+    #[inline]
+    fn position(&self, random: &mut dyn Rng) -> CellPosition {
+        random.generate_cell_position(self.width, self.height)
+    }
+}
+
+/// Running totals of weights, to pick an index in proportion to its
+/// weight.
+pub(crate) struct CumulativeWeights(Vec<f64>);
+
+impl CumulativeWeights {
+    /// `None` unless some weight is positive. No weight may be negative.
+    pub(crate) fn new<I: IntoIterator<Item=f64>>(weights: I) -> Option<CumulativeWeights> {
+        let totals: Vec<f64> = weights.into_iter()
+            .scan(0.0, |total, weight| {
+                *total += weight;
+                Some(*total)
+            })
+            .collect();
+        if totals.last().is_some_and(|total| *total > 0.0) {
+            Some(CumulativeWeights(totals))
+        } else {
+            None
+        }
+    }
+
+    /// An index drawn in proportion to its weight. Indices weighing 0 are
+    /// never drawn.
+    #[inline]
+    pub(crate) fn pick<R: Rng + ?Sized>(&self, random: &mut R) -> usize {
+        let last = self.0.len() - 1;
+        let weight = random.generate_float() * self.0[last];
+        self.0.partition_point(|total| *total <= weight).min(last)
+    }
+}
+
+/// Every cell in proportion to its weight.
+pub struct WeightMap {
+    width: usize,
+    /// The weights, row by row.
+    weights: CumulativeWeights,
+}
+
+impl WeightMap {
+    /// Weights listed row by row. Negative ones count as 0.
+    pub fn new<I: IntoIterator<Item=f64>>(width: usize, weights: I) -> Result<WeightMap, String> {
+        let weights = CumulativeWeights::new(weights.into_iter().map(|weight| weight.max(0.0)))
+            .ok_or_else(|| "No cell has an inflow weight".to_string())?;
+        Ok(WeightMap { width, weights })
+    }
+
+    /// The level of every cell in `mask` as its weight.
+    pub fn from_mask(mask: &Mask) -> Result<WeightMap, String> {
+        WeightMap::new(mask.width(), mask.cells().map(|(position, _)| mask.level(&position)))
+    }
+
+    fn from_fn<F: Fn(usize, usize) -> f64>(width: usize, height: usize, weight: F) -> WeightMap {
+        WeightMap::new(width, (0..width * height).map(|index| weight(index % width, index / width)))
+            .expect("Built-in weights are positive somewhere")
+    }
+}
+
+impl InflowStrategy for WeightMap {
+    #[inline]
+    fn position(&self, random: &mut dyn Rng) -> CellPosition {
+        let index = self.weights.pick(random);
+        CellPosition(index % self.width, index / self.width)
+    }
+}
+
+/// The inflow strategies a run can pick by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inflow {
+    Uniform,
+    /// Weights rising linearly from one edge to the other along `x`, or
+    /// along `y` if the flag is set.
+    Gradient(bool),
+    /// Gaussian bumps of the given radius around random cells.
+    Hotspots { count: usize, radius: usize },
+    /// Only the cells within the given distance of an edge.
+    Border(usize),
+}
+
+impl Inflow {
+    /// The strategy for a `width` by `height` pond, drawing what it needs,
+    /// such as the hotspots, from `random`.
+    pub fn strategy<R: Rng>(&self, width: usize, height: usize, random: &mut R) -> Box<dyn InflowStrategy> {
+        match *self {
+            Inflow::Uniform => Box::new(Uniform::new(width, height)),
+            Inflow::Gradient(false) => Box::new(WeightMap::from_fn(width, height, |x, _| (x + 1) as f64)),
+            Inflow::Gradient(true) => Box::new(WeightMap::from_fn(width, height, |_, y| (y + 1) as f64)),
+            Inflow::Hotspots { count, radius } => {
+                let centers: Vec<CellPosition> = (0..count)
+                    .map(|_| random.generate_cell_position(width, height))
+                    .collect();
+                let spread = 2.0 * (radius * radius) as f64;
+                Box::new(WeightMap::from_fn(width, height, |x, y| {
+                    centers.iter()
+                        .map(|center| {
+                            // Distances wrap around, like the pond.
+                            let dx = x.abs_diff(center.0).min(width - x.abs_diff(center.0));
+                            let dy = y.abs_diff(center.1).min(height - y.abs_diff(center.1));
+                            (-((dx * dx + dy * dy) as f64) / spread).exp()
+                        })
+                        .sum()
+                }))
+            },
+            Inflow::Border(distance) => Box::new(WeightMap::from_fn(width, height, |x, y| {
+                let edge = x.min(width - 1 - x).min(y).min(height - 1 - y);
+                if edge < distance { 1.0 } else { 0.0 }
+            })),
+        }
+    }
+}
+
+impl fmt::Display for Inflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Inflow::Uniform => write!(f, "uniform"),
+            Inflow::Gradient(false) => write!(f, "gradient-x"),
+            Inflow::Gradient(true) => write!(f, "gradient-y"),
+            Inflow::Hotspots { count, radius } => write!(f, "hotspots:{}:{}", count, radius),
+            Inflow::Border(distance) => write!(f, "border:{}", distance),
+        }
+    }
+}
+
+/// `uniform`, `gradient-x`, `gradient-y`, `hotspots:<count>:<radius>` or
+/// `border:<distance>`.
+impl FromStr for Inflow {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Inflow, String> {
+        let invalid = || format!("Unknown inflow {}", name);
+        let mut parts = name.split(':');
+        let kind = parts.next().unwrap_or("");
+        let numbers = parts.map(|part| part.parse::<usize>().ok().filter(|number| *number > 0))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        match (kind, numbers.as_slice()) {
+            ("uniform", []) => Ok(Inflow::Uniform),
+            ("gradient-x", []) => Ok(Inflow::Gradient(false)),
+            ("gradient-y", []) => Ok(Inflow::Gradient(true)),
+            ("hotspots", [count, radius]) => Ok(Inflow::Hotspots { count: *count, radius: *radius }),
+            ("border", [distance]) => Ok(Inflow::Border(*distance)),
+            _ => Err(invalid()),
+        }
+    }
+}
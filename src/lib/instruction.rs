@@ -1,7 +1,8 @@
+use std::fmt;
 use std::slice::Iter;
 
-#[derive(PartialEq)]
-pub(crate) enum Instruction {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
     Zero,
     Fwd,
     Back,
@@ -21,7 +22,7 @@ pub(crate) enum Instruction {
 }
 
 impl Instruction {
-    pub(crate) fn iterator() -> Iter<'static, Instruction> {
+    pub fn iterator() -> Iter<'static, Instruction> {
         static INSTRUCTIONS: [Instruction; 16] = [
             Instruction::Zero,
             Instruction::Fwd,
@@ -40,7 +41,7 @@ impl Instruction {
             Instruction::Share,
             Instruction::Stop,
         ];
-        INSTRUCTIONS.into_iter()
+        INSTRUCTIONS.iter()
     }
 }
 
@@ -66,4 +67,27 @@ impl From<u8> for Instruction {
             _ => panic!("Can't happen"),
         }
     }
-}
\ No newline at end of file
+}
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mnemonic = match self {
+            Instruction::Zero => "ZERO",
+            Instruction::Fwd => "FWD",
+            Instruction::Back => "BACK",
+            Instruction::Inc => "INC",
+            Instruction::Dec => "DEC",
+            Instruction::ReadGenome => "READG",
+            Instruction::WriteGenome => "WRITEG",
+            Instruction::ReadBuffer => "READB",
+            Instruction::WriteBuffer => "WRITEB",
+            Instruction::Loop => "LOOP",
+            Instruction::Rep => "REP",
+            Instruction::Turn => "TURN",
+            Instruction::Xchg => "XCHG",
+            Instruction::Kill => "KILL",
+            Instruction::Share => "SHARE",
+            Instruction::Stop => "STOP",
+        };
+        f.pad(mnemonic)
+    }
+}
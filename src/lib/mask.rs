@@ -0,0 +1,160 @@
+use std::str::{self, FromStr};
+use cell::CellPosition;
+
+/// A value per cell of the pond, row by row from `y = 0`, between 0 and
+/// `max_value`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    max_value: u16,
+    values: Vec<u16>,
+}
+
+impl Mask {
+    /// Reads a PGM or PPM image, binary or plain, or else a text mask. The
+    /// first row of an image is `y = 0` and a color is read as the mean of
+    /// its channels.
+    pub fn read(bytes: &[u8]) -> Result<Mask, String> {
+        match bytes.get(..2) {
+            Some(b"P2") => read_netpbm(bytes, 1, false),
+            Some(b"P3") => read_netpbm(bytes, 3, false),
+            Some(b"P5") => read_netpbm(bytes, 1, true),
+            Some(b"P6") => read_netpbm(bytes, 3, true),
+            _ => str::from_utf8(bytes).map_err(|_| "Not an image nor a text mask".to_string())?.parse(),
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn max_value(&self) -> u16 {
+        self.max_value
+    }
+
+    #[inline]
+    pub fn get(&self, position: &CellPosition) -> u16 {
+        self.values[position.1 * self.width + position.0]
+    }
+
+    /// The value as a fraction of `max_value`.
+    #[inline]
+    pub fn level(&self, position: &CellPosition) -> f64 {
+        f64::from(self.get(position)) / f64::from(self.max_value)
+    }
+
+    /// Every cell with its value, row by row.
+    #[inline]
+    pub fn cells<'a>(&'a self) -> impl Iterator<Item=(CellPosition, u16)> + 'a {
+        let width = self.width;
+        self.values.iter().enumerate()
+            .map(move |(index, value)| (CellPosition(index % width, index / width), *value))
+    }
+
+    /// Fails unless the mask covers a `width` by `height` pond exactly.
+    pub fn fits(&self, width: usize, height: usize) -> Result<(), String> {
+        if self.width != width || self.height != height {
+            return Err(format!("The mask is {}x{} but the pond is {}x{}",
+                               self.width, self.height, width, height));
+        }
+        Ok(())
+    }
+}
+
+/// A text mask has one line per row, `#` for a set cell (1) and `.` for a
+/// clear one (0).
+impl FromStr for Mask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mask, String> {
+        let mut mask = Mask { width: 0, height: 0, max_value: 1, values: Vec::new() };
+        for line in s.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()) {
+            let start = mask.values.len();
+            for symbol in line.chars() {
+                mask.values.push(match symbol {
+                    '#' => 1,
+                    '.' => 0,
+                    _ => return Err(format!("Invalid symbol {} on row {}", symbol, mask.height)),
+                });
+            }
+            if mask.height == 0 {
+                mask.width = mask.values.len();
+            } else if mask.values.len() - start != mask.width {
+                return Err(format!("Row {} isn't {} cells wide", mask.height, mask.width));
+            }
+            mask.height += 1;
+        }
+        if mask.values.is_empty() {
+            return Err("Empty mask".to_string());
+        }
+        Ok(mask)
+    }
+}
+
+/// Reads a number of a Netpbm image, skipping whitespace and comments, and
+/// returns where the next one would start.
+fn read_number(bytes: &[u8], mut start: usize) -> Result<(usize, usize), String> {
+    loop {
+        match bytes.get(start) {
+            Some(b'#') => while bytes.get(start).is_some_and(|byte| *byte != b'\n') {
+                start += 1;
+            },
+            Some(byte) if byte.is_ascii_whitespace() => start += 1,
+            _ => break,
+        }
+    }
+    let end = start + bytes[start..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let field = str::from_utf8(&bytes[start..end]).ok()
+        .and_then(|field| field.parse().ok())
+        .ok_or("Invalid image header")?;
+    Ok((field, end))
+}
+
+fn read_netpbm(bytes: &[u8], channels: usize, binary: bool) -> Result<Mask, String> {
+    let (width, next) = read_number(bytes, 2)?;
+    let (height, next) = read_number(bytes, next)?;
+    let (max_value, next) = read_number(bytes, next)?;
+    if width == 0 || height == 0 || max_value == 0 || max_value > usize::from(u16::MAX) {
+        return Err(format!("Invalid image of {}x{} with maximum {}", width, height, max_value));
+    }
+    let count = width * height * channels;
+    let samples: Vec<usize> = if binary {
+        // A single whitespace separates the header from the samples.
+        let data = bytes.get(next + 1..).unwrap_or(&[]);
+        let size = if max_value > 255 { 2 } else { 1 };
+        if data.len() < count * size {
+            return Err("Truncated image".to_string());
+        }
+        data.chunks(size).take(count)
+            .map(|sample| sample.iter().fold(0, |value, byte| (value << 8) | usize::from(*byte)))
+            .collect()
+    } else {
+        let mut samples = Vec::with_capacity(count);
+        let mut next = next;
+        while samples.len() < count {
+            let (sample, end) = read_number(bytes, next).map_err(|_| "Truncated image")?;
+            samples.push(sample);
+            next = end;
+        }
+        samples
+    };
+    if samples.iter().any(|sample| *sample > max_value) {
+        return Err(format!("Image sample above the maximum {}", max_value));
+    }
+    Ok(Mask {
+        width,
+        height,
+        max_value: max_value as u16,
+        values: samples.chunks(channels)
+            .map(|pixel| (pixel.iter().sum::<usize>() / channels) as u16)
+            .collect(),
+    })
+}
@@ -1,10 +1,34 @@
+mod active_cells;
+mod aggregates;
+mod alignment;
+mod batch;
+mod bench;
 mod cell;
 mod cell_pond;
 mod cell_vm;
+mod checkpoint;
+mod codec;
+mod config;
+mod disturbance;
+mod events;
 mod genome;
+mod genome_analysis;
+mod genome_bank;
+mod genome_store;
+mod inflow;
 mod instruction;
+mod mask;
+mod motifs;
+mod parallel;
 mod random_generator;
+mod replay;
+mod reproduction_log;
+mod rng;
+mod schedule;
+mod simulation;
 mod statistics;
+mod sweep;
+mod topology;
 
 const FAILED_KILL_PENALTY: usize = 1/3;
 const MUTATION_RATE: usize = 5000;
@@ -16,9 +40,32 @@ const INFLOW_RATE_BASE: usize = 600;
 pub const INFLOW_FREQUENCY: usize = 100;
 pub const REPORT_FREQUENCY: usize = 200000;
 
-pub use cell::CellIdGenerator;
+pub use alignment::{Alignment, Edit};
+pub use batch::{Batch, Summary};
+pub use bench::{Benchmark, BenchmarkReport};
+pub use cell::{CellId, CellIdGenerator};
 pub use cell_pond::CellPond;
 pub use cell_vm::CellVM;
+pub use checkpoint::Checkpoint;
+pub use config::{Config, Scheduling};
+pub use disturbance::{Disturbance, Disturbances, Region, Shape, Timing};
+pub use events::{CellHeader, Event, EventKind, EventLog};
 pub use genome::Genome;
-pub use random_generator::RandomGenerator;
-pub use statistics::Statistics;
\ No newline at end of file
+pub use genome_analysis::{ClassCensus, EcologicalClass, GenomeAnalysis};
+pub use genome_bank::GenomeBank;
+pub use genome_store::{GenomeBuffer, GenomeStore};
+pub use inflow::{Inflow, InflowStrategy, Uniform, WeightMap};
+pub use instruction::Instruction;
+pub use mask::Mask;
+pub use motifs::{Motif, MotifOptions, MotifReport};
+pub use parallel::ParallelExecutor;
+pub use random_generator::{RandomGenerator, RandomStreams};
+pub use rng::{ChaCha, Pcg64, Rng, RngKind, SplitMix64, Xorshift128Plus};
+pub use replay::Replay;
+pub use reproduction_log::{LogFormat, ReproductionLog, ReproductionRecord};
+pub use schedule::{Parameter, Parameters, Schedule, Schedules};
+pub use simulation::{Phase, Sample, Simulation, TickHooks};
+pub use statistics::{
+    LineageCounters, LineageKey, LineageStatistics, NgramCounter, PositionHistogram, Statistics};
+pub use sweep::{Condition, Index, Sweep};
+pub use topology::{Boundary, Facing, Topology};
\ No newline at end of file
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt;
+use cell::CellId;
+use cell_pond::CellPond;
+use genome_analysis::GenomeAnalysis;
+use genome_store::GenomeBuffer;
+use instruction::Instruction;
+
+const MAX_MOTIF_LENGTH: usize = 16;
+const LINEAGES_PER_MOTIF: usize = 3;
+
+pub struct MotifOptions {
+    pub length: usize,
+    pub top: usize,
+    pub min_generation: usize,
+    pub conservation: f64,
+}
+
+impl Default for MotifOptions {
+    fn default() -> MotifOptions {
+        MotifOptions {
+            length: 4,
+            top: 10,
+            min_generation: 0,
+            conservation: 0.5,
+        }
+    }
+}
+
+pub struct Motif {
+    pub instructions: Vec<Instruction>,
+    pub genomes: usize,
+    pub occurrences: usize,
+    pub lineages: Vec<(CellId, usize)>,
+    pub total_lineages: usize,
+}
+
+impl fmt::Display for Motif {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if i > 0 {
+                write!(f, ">")?;
+            }
+            write!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+struct Carrier {
+    lineage: CellId,
+    code: Vec<u8>,
+}
+
+impl Carrier {
+    #[inline]
+    fn occurrences(&self, motif: &[u8]) -> usize {
+        if motif.len() > self.code.len() {
+            0
+        } else {
+            self.code.windows(motif.len()).filter(|window| *window == motif).count()
+        }
+    }
+
+    /// Instructions right before (or right after) each occurrence of `motif`.
+    #[inline]
+    fn flanks(&self, motif: &[u8], before: bool) -> Vec<u8> {
+        if motif.len() >= self.code.len() {
+            return Vec::new();
+        }
+        self.code.windows(motif.len())
+            .enumerate()
+            .filter(|(_, window)| *window == motif)
+            .filter_map(|(start, _)| if before {
+                if start > 0 { Some(self.code[start - 1]) } else { None }
+            } else {
+                self.code.get(start + motif.len()).cloned()
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct NgramCount {
+    genomes: usize,
+    occurrences: usize,
+    last_genome: Option<usize>,
+}
+
+/// Most conserved instruction motifs among the live genomes of a pond. Only
+/// the part of each genome up to its first reachable STOP is scanned. The
+/// most frequent n-grams are used as seeds and greedily extended while at
+/// least `conservation` of the genomes carrying the seed still carry the
+/// extension, so longer shared substrings show up as a single motif.
+pub struct MotifReport {
+    pub genomes: usize,
+    pub motifs: Vec<Motif>,
+}
+
+impl MotifReport {
+    pub fn new<G: GenomeBuffer>(pond: &CellPond<G>, options: &MotifOptions) -> MotifReport {
+        let length = options.length.clamp(1, MAX_MOTIF_LENGTH);
+        let carriers: Vec<Carrier> = pond.active_cells()
+            .filter(|cell| cell.generation >= options.min_generation)
+            .map(|cell| {
+                let effective_length = GenomeAnalysis::new(cell.genome()).effective_length;
+                Carrier {
+                    lineage: cell.lineage.clone(),
+                    code: cell.genome().instructions()
+                        .take(effective_length)
+                        .map(|instruction| instruction as u8)
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let mut counts: HashMap<u64, NgramCount> = HashMap::new();
+        for (index, carrier) in carriers.iter().enumerate() {
+            if carrier.code.len() < length {
+                continue;
+            }
+            for window in carrier.code.windows(length) {
+                let count = counts.entry(pack(window)).or_default();
+                count.occurrences += 1;
+                if count.last_genome != Some(index) {
+                    count.last_genome = Some(index);
+                    count.genomes += 1;
+                }
+            }
+        }
+        let mut seeds: Vec<(u64, usize)> = counts.iter()
+            .filter(|(_, count)| count.genomes > 1)
+            .map(|(key, count)| (*key, count.genomes))
+            .collect();
+        seeds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut motifs: Vec<Motif> = Vec::with_capacity(options.top);
+        for (key, _) in seeds {
+            if motifs.len() >= options.top {
+                break;
+            }
+            let code = extend(&carriers, unpack(key, length), options.conservation);
+            let instructions: Vec<Instruction> = code.iter()
+                .map(|nibble| Instruction::from(*nibble))
+                .collect();
+            let known = motifs.iter().any(|motif| {
+                motif.instructions.len() >= instructions.len() &&
+                    motif.instructions.windows(instructions.len())
+                        .any(|window| window == instructions.as_slice())
+            });
+            if !known {
+                motifs.push(motif(&carriers, instructions, &code));
+            }
+        }
+
+        MotifReport {
+            genomes: carriers.len(),
+            motifs,
+        }
+    }
+}
+
+impl fmt::Display for MotifReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (rank, motif) in self.motifs.iter().enumerate() {
+            write!(f, "{},{},{:04},{},{}",
+                   rank + 1,
+                   motif,
+                   motif.genomes as f64 / self.genomes as f64,
+                   motif.occurrences,
+                   motif.total_lineages)?;
+            for (lineage, genomes) in motif.lineages.iter() {
+                write!(f, ",{}:{}", lineage, genomes)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn pack(code: &[u8]) -> u64 {
+    code.iter().fold(0, |key, nibble| (key << 4) | u64::from(*nibble))
+}
+
+#[inline]
+fn unpack(key: u64, length: usize) -> Vec<u8> {
+    (0..length).rev().map(|i| ((key >> (4 * i)) & 0x0f) as u8).collect()
+}
+
+fn extend(carriers: &[Carrier], mut code: Vec<u8>, conservation: f64) -> Vec<u8> {
+    let mut holders: Vec<&Carrier> = carriers.iter()
+        .filter(|carrier| carrier.occurrences(&code) > 0)
+        .collect();
+    let threshold = ((holders.len() as f64 * conservation).ceil() as usize).max(2);
+    let mut prepend = false;
+    while code.len() < MAX_MOTIF_LENGTH {
+        let mut flanks = [0usize; 16];
+        for carrier in holders.iter() {
+            let mut seen = [false; 16];
+            for nibble in carrier.flanks(&code, prepend) {
+                if !seen[nibble as usize] {
+                    seen[nibble as usize] = true;
+                    flanks[nibble as usize] += 1;
+                }
+            }
+        }
+        let (nibble, genomes) = flanks.iter()
+            .enumerate()
+            .fold((0, 0), |best, (nibble, genomes)|
+                if *genomes > best.1 { (nibble as u8, *genomes) } else { best });
+        if genomes >= threshold {
+            if prepend {
+                code.insert(0, nibble);
+            } else {
+                code.push(nibble);
+            }
+            holders.retain(|carrier| carrier.occurrences(&code) > 0);
+        } else if !prepend {
+            prepend = true;
+        } else {
+            break;
+        }
+    }
+    code
+}
+
+fn motif(carriers: &[Carrier], instructions: Vec<Instruction>, code: &[u8]) -> Motif {
+    let mut genomes = 0;
+    let mut occurrences = 0;
+    let mut lineages: HashMap<CellId, usize> = HashMap::new();
+    for carrier in carriers {
+        let found = carrier.occurrences(code);
+        if found > 0 {
+            genomes += 1;
+            occurrences += found;
+            *lineages.entry(carrier.lineage.clone()).or_insert(0) += 1;
+        }
+    }
+    let total_lineages = lineages.len();
+    let mut lineages: Vec<(CellId, usize)> = lineages.into_iter().collect();
+    lineages.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    lineages.truncate(LINEAGES_PER_MOTIF);
+    Motif {
+        instructions,
+        genomes,
+        occurrences,
+        lineages,
+        total_lineages,
+    }
+}
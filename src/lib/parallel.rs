@@ -0,0 +1,285 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use aggregates::{Aggregates, Contribution};
+use cell::{Cell, CellIdGenerator, CellPosition};
+use cell_pond::{is_obstacle, CellPond, Grid};
+use cell_vm::CellVM;
+use config::Scheduling;
+use disturbance::{boosted_mutation_rate, MutationBoost};
+use genome::Genome;
+use genome_store::{GenomeBuffer, GenomeStore};
+use random_generator::RandomStreams;
+use rng::Rng;
+use simulation::Simulation;
+use statistics::Statistics;
+use topology::{Boundary, Facing, Topology};
+
+struct SharedGrid<G: GenomeBuffer> {
+    cells: *mut Cell<G>,
+    width: usize,
+    genome_store: Option<Mutex<GenomeStore>>,
+    obstacles: Option<Arc<[bool]>>,
+    boosts: Vec<MutationBoost>,
+}
+
+// Tiles running at the same time never reach the same cell, see
+// `ParallelExecutor`.
+unsafe impl<G: GenomeBuffer> Send for SharedGrid<G> {}
+unsafe impl<G: GenomeBuffer> Sync for SharedGrid<G> {}
+
+#[derive(Clone, Copy)]
+struct Environment {
+    width: usize,
+    height: usize,
+    mutation_rate: usize,
+    topology: Topology,
+    boundary: Boundary,
+}
+
+struct Tile {
+    color: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    executions: usize,
+    random: RandomStreams,
+    id_generator: CellIdGenerator,
+    statistics: Statistics,
+    aggregates: Aggregates,
+}
+
+impl Tile {
+    #[inline]
+    fn generate_cell_position(&mut self) -> CellPosition {
+        let position = self.random.selection.generate_cell_position(self.width, self.height);
+        CellPosition(self.x + position.0, self.y + position.1)
+    }
+
+    fn execute<G: GenomeBuffer>(&mut self, grid: &SharedGrid<G>, environment: Environment) {
+        let mut view = TileView {
+            grid,
+            environment,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            touched: Vec::with_capacity(4),
+        };
+        for _ in 0..self.executions {
+            let position = self.generate_cell_position();
+            CellVM::new(position,
+                        &mut view,
+                        &mut self.id_generator,
+                        &mut self.random,
+                        &mut self.statistics).execute();
+            view.update_aggregates(&mut self.aggregates);
+        }
+    }
+}
+
+struct TileView<'a, G: 'a + GenomeBuffer> {
+    grid: &'a SharedGrid<G>,
+    environment: Environment,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    touched: Vec<(usize, Contribution)>,
+}
+
+impl<'a, G: GenomeBuffer> TileView<'a, G> {
+    #[inline]
+    fn update_aggregates(&mut self, aggregates: &mut Aggregates) {
+        for (index, before) in self.touched.drain(..) {
+            let after = unsafe { &*self.grid.cells.add(index) };
+            aggregates.update(before, Contribution::from(after));
+        }
+    }
+
+    #[inline]
+    fn reaches(&self, position: &CellPosition) -> bool {
+        let (width, height) = (self.environment.width, self.environment.height);
+        let dx = (position.0 + width + 1 - self.x) % width;
+        let dy = (position.1 + height + 1 - self.y) % height;
+        dx < self.width + 2 && dy < self.height + 2
+    }
+}
+
+impl<'a, G: GenomeBuffer> Grid for TileView<'a, G> {
+    type Buffer = G;
+
+    #[inline]
+    fn cell(&mut self, position: &CellPosition) -> &mut Cell<G> {
+        debug_assert!(self.reaches(position));
+        let index = position.1 * self.grid.width + position.0;
+        let cell = unsafe { &mut *self.grid.cells.add(index) };
+        if !self.touched.iter().any(|(touched, _)| *touched == index) {
+            self.touched.push((index, Contribution::from(&*cell)));
+        }
+        cell
+    }
+
+    #[inline]
+    fn neighbor_position(&self, position: &CellPosition, facing: &Facing) -> Option<CellPosition> {
+        let Environment { width, height, topology, boundary, .. } = self.environment;
+        topology.neighbor_position(position, facing, width, height, boundary)
+            .filter(|neighbor| !is_obstacle(&self.grid.obstacles, neighbor.1 * width + neighbor.0))
+    }
+
+    #[inline]
+    fn mutation_rate_at(&self, position: &CellPosition) -> usize {
+        let Environment { width, height, mutation_rate, .. } = self.environment;
+        boosted_mutation_rate(&self.grid.boosts, mutation_rate, position, width, height)
+    }
+
+    #[inline]
+    fn topology(&self) -> Topology {
+        self.environment.topology
+    }
+
+    #[inline]
+    fn share(&mut self, genome: &Genome) -> G {
+        match self.grid.genome_store {
+            Some(ref store) => G::intern(&mut store.lock().expect("A tile panicked"), genome),
+            None => G::new(genome.clone()),
+        }
+    }
+}
+
+/// Executes cells on several threads. The pond is split into a grid of
+/// tiles with an even number of columns and rows, colored like a 2x2
+/// checkerboard. Tiles of the same color are at least one tile apart, so
+/// as a cell only touches itself and its neighbors they can run at the
+/// same time. A round runs every color in turn, each tile executing a
+/// share of the round proportional to its area, with its own random
+/// generator, id stream and statistics. The outcome only depends on the
+/// seed and the tile grid, not on the number of threads.
+///
+/// Time only moves a round at a time: the inflows, schedule changes and
+/// disturbances due during a round come after all of its executions. The
+/// round has to divide the report frequency, so that every report counts
+/// whole rounds.
+pub struct ParallelExecutor {
+    tiles: Vec<Tile>,
+    colors: [usize; 5],
+    round: usize,
+    threads: usize,
+}
+
+impl ParallelExecutor {
+    pub fn new<G: GenomeBuffer>(columns: usize,
+               rows: usize,
+               round: usize,
+               threads: usize,
+               simulation: &mut Simulation<G>) -> Result<ParallelExecutor, String> {
+        let Simulation { ref pond, ref mut random, ref mut id_generator, ref statistics, .. } =
+            *simulation;
+        if columns < 2 || rows < 2 || !columns.is_multiple_of(2) || !rows.is_multiple_of(2) {
+            return Err(format!("The tile grid must have an even number of columns and rows, got {}x{}",
+                               columns, rows));
+        }
+        let (width, height) = (pond.width(), pond.height());
+        if width / columns < 2 || height / rows < 2 {
+            return Err(format!("Tiles of a {}x{} grid are too small", columns, rows));
+        }
+        if simulation.config.scheduling != Scheduling::Uniform {
+            return Err("Tiles only support uniform scheduling".to_string());
+        }
+        let report_frequency = simulation.config.report_frequency;
+        if round == 0 || !report_frequency.is_multiple_of(round) {
+            return Err(format!("The round must divide the report frequency {}, got {}", report_frequency, round));
+        }
+        if threads == 0 {
+            return Err("At least one thread is needed".to_string());
+        }
+        let count = columns * rows;
+        let mut id_generators = id_generator.interleave(count).into_iter();
+        let area = width * height;
+        let mut assigned = 0;
+        let mut tiles = Vec::with_capacity(count);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column * width / columns;
+                let y = row * height / rows;
+                let tile_width = (column + 1) * width / columns - x;
+                let tile_height = (row + 1) * height / rows - y;
+                let executions = round * tile_width * tile_height / area;
+                assigned += executions;
+                tiles.push(Tile {
+                    color: (column % 2) + 2 * (row % 2),
+                    x,
+                    y,
+                    width: tile_width,
+                    height: tile_height,
+                    executions,
+                    random: random.fork(),
+                    id_generator: id_generators.next().expect("One id generator per tile"),
+                    statistics: statistics.worker(),
+                    aggregates: Aggregates::default(),
+                });
+            }
+        }
+        for tile in tiles.iter_mut().take(round - assigned) {
+            tile.executions += 1;
+        }
+        tiles.sort_by_key(|tile| tile.color);
+        let mut colors = [0; 5];
+        for color in 0..4 {
+            colors[color + 1] = colors[color] +
+                tiles.iter().filter(|tile| tile.color == color).count();
+        }
+        Ok(ParallelExecutor { tiles, colors, round, threads })
+    }
+
+    /// Number of cell executions in a round.
+    #[inline]
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    #[inline]
+    pub fn id_generators(&self) -> impl Iterator<Item=&CellIdGenerator> {
+        self.tiles.iter().map(|tile| &tile.id_generator)
+    }
+
+    pub fn execute_round<G: GenomeBuffer>(&mut self, pond: &mut CellPond<G>, statistics: &mut Statistics) {
+        let environment = Environment {
+            width: pond.width(),
+            height: pond.height(),
+            mutation_rate: pond.mutation_rate(),
+            topology: pond.topology(),
+            boundary: pond.boundary(),
+        };
+        let grid = SharedGrid {
+            cells: pond.cells_pointer(),
+            width: pond.width(),
+            genome_store: pond.take_genome_store().map(Mutex::new),
+            obstacles: pond.obstacles(),
+            boosts: pond.boosts().to_vec(),
+        };
+        for tile in self.tiles.iter_mut() {
+            tile.statistics.clock = statistics.clock;
+        }
+        for color in 0..4 {
+            let tiles = &mut self.tiles[self.colors[color]..self.colors[color + 1]];
+            let chunk = tiles.len().div_ceil(self.threads).max(1);
+            let grid = &grid;
+            thread::scope(|scope| {
+                for chunk in tiles.chunks_mut(chunk) {
+                    scope.spawn(move || {
+                        for tile in chunk.iter_mut() {
+                            tile.execute(grid, environment);
+                        }
+                    });
+                }
+            });
+        }
+        pond.restore_genome_store(
+            grid.genome_store.map(|store| store.into_inner().expect("A tile panicked")));
+        for tile in self.tiles.iter_mut() {
+            statistics.merge(&mut tile.statistics);
+            pond.merge_aggregates(&mut tile.aggregates);
+        }
+    }
+}
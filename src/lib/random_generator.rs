@@ -1,33 +1,114 @@
-use cell::CellPosition;
-use super::{POND_WIDTH, POND_HEIGHT};
+use rng::{ChaCha, Pcg64, Rng, RngKind, SplitMix64, Xorshift128Plus};
+
+/// The generator of a run, one of the built-in ones picked at run time.
+#[derive(Clone)]
+pub enum RandomGenerator {
+    Xorshift(Xorshift128Plus),
+    Pcg(Pcg64),
+    SplitMix(SplitMix64),
+    ChaCha(Box<ChaCha>),
+}
 
-pub struct RandomGenerator([usize; 2]);
 impl RandomGenerator {
     #[inline]
-    pub fn new(fseed: usize, sseed: usize) -> RandomGenerator {
-        RandomGenerator([fseed, sseed])
+    pub fn new(fseed: u64, sseed: u64) -> RandomGenerator {
+        RandomGenerator::Xorshift(Xorshift128Plus::new(fseed, sseed))
+    }
+
+    /// A generator of the given kind, its state expanded from `seed` with
+    /// SplitMix64.
+    pub fn from_seed(kind: RngKind, seed: u64) -> RandomGenerator {
+        match kind {
+            RngKind::Xorshift => RandomGenerator::Xorshift(Xorshift128Plus::from_seed(seed)),
+            RngKind::Pcg => RandomGenerator::Pcg(Pcg64::from_seed(seed)),
+            RngKind::SplitMix => RandomGenerator::SplitMix(SplitMix64::new(seed)),
+            RngKind::ChaCha => RandomGenerator::ChaCha(Box::new(ChaCha::from_seed(seed))),
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> RngKind {
+        match self {
+            RandomGenerator::Xorshift(_) => RngKind::Xorshift,
+            RandomGenerator::Pcg(_) => RngKind::Pcg,
+            RandomGenerator::SplitMix(_) => RngKind::SplitMix,
+            RandomGenerator::ChaCha(_) => RngKind::ChaCha,
+        }
     }
 
+    /// Moves far enough ahead that the draws before and after never meet,
+    /// see the `jump` of each generator.
     #[inline]
-    pub fn generate_integer(&mut self) -> usize {
-        let mut x = self.0[0];
-        let y = self.0[1];
-        self.0[0] = y;
-        x ^= x << 23;
-        self.0[1] = x ^ y ^ (x >> 17) ^ (y >> 26);
-        self.0[1].wrapping_add(y)
+    pub fn jump(&mut self) {
+        match self {
+            RandomGenerator::Xorshift(rng) => rng.jump(),
+            RandomGenerator::Pcg(rng) => rng.jump(),
+            RandomGenerator::SplitMix(rng) => rng.jump(),
+            RandomGenerator::ChaCha(rng) => rng.jump(),
+        }
     }
 
+    /// An independent generator of the same kind, seeded from this one.
     #[inline]
-    pub fn generate_boolean(&mut self) -> bool {
-        (self.generate_integer() & 0x80) > 0
+    pub fn fork(&mut self) -> RandomGenerator {
+        let seed = self.next_u64();
+        RandomGenerator::from_seed(self.kind(), seed)
     }
+}
 
+impl Rng for RandomGenerator {
     #[inline]
-    pub fn generate_cell_position(&mut self) -> CellPosition {
-        let n = self.generate_integer();
-        let x = n % POND_WIDTH;
-        let y = ((n / POND_HEIGHT) >> 1) % POND_HEIGHT;
-        CellPosition(x, y)
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            RandomGenerator::Xorshift(rng) => rng.next_u64(),
+            RandomGenerator::Pcg(rng) => rng.next_u64(),
+            RandomGenerator::SplitMix(rng) => rng.next_u64(),
+            RandomGenerator::ChaCha(rng) => rng.next_u64(),
+        }
+    }
+}
+
+/// One generator per kind of random decision, so that changing how often
+/// one kind is drawn leaves the draws of the others untouched.
+pub struct RandomStreams<R: Rng = RandomGenerator> {
+    /// Which cell executes.
+    pub selection: R,
+    /// Whether and how an instruction mutates.
+    pub mutation: R,
+    /// Where genomes flow in and what they are, the starting pond included.
+    pub inflow: R,
+    /// The threshold of `Cell::can_be_accessed`.
+    pub interaction: R,
+    /// When and where random disturbances strike.
+    pub disturbance: R,
+}
+
+impl RandomStreams {
+    /// Streams taken from consecutive jumps of `generator`.
+    pub fn new(generator: RandomGenerator) -> RandomStreams {
+        let mut next = generator;
+        let mut stream = || {
+            let stream = next.clone();
+            next.jump();
+            stream
+        };
+        RandomStreams {
+            selection: stream(),
+            mutation: stream(),
+            inflow: stream(),
+            interaction: stream(),
+            disturbance: stream(),
+        }
+    }
+
+    /// Independent streams seeded from these ones.
+    pub fn fork(&mut self) -> RandomStreams {
+        RandomStreams {
+            selection: self.selection.fork(),
+            mutation: self.mutation.fork(),
+            inflow: self.inflow.fork(),
+            interaction: self.interaction.fork(),
+            disturbance: self.disturbance.fork(),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,160 @@
+use cell::CellIdGenerator;
+use cell_pond::CellPond;
+use checkpoint::Checkpoint;
+use events::Event;
+
+/// Rebuilds the pond at any tick from a checkpoint and the event log of the
+/// same run. The checkpoint may have been taken at any point of the log,
+/// even in the middle of a tick, since it counts the events it includes and
+/// events can be reverted as well as applied.
+pub struct Replay {
+    pond: CellPond,
+    clock: usize,
+    events: Vec<Event>,
+    applied: usize,
+}
+
+impl Replay {
+    pub fn new(checkpoint: Checkpoint, events: Vec<Event>) -> Replay {
+        let applied = checkpoint.events.min(events.len());
+        Replay {
+            pond: checkpoint.pond,
+            clock: checkpoint.clock,
+            events,
+            applied,
+        }
+    }
+
+    #[inline]
+    pub fn clock(&self) -> usize {
+        self.clock
+    }
+
+    #[inline]
+    pub fn pond(&self) -> &CellPond {
+        &self.pond
+    }
+
+    /// The last tick with recorded events.
+    #[inline]
+    pub fn last_tick(&self) -> usize {
+        self.events.last().map(|event| event.tick).unwrap_or(self.clock)
+    }
+
+    /// Moves the pond to its state right after everything that happened at
+    /// `tick`.
+    pub fn seek(&mut self, tick: usize) {
+        while self.applied < self.events.len() && self.events[self.applied].tick <= tick {
+            let event = &self.events[self.applied];
+            event.apply(self.pond.cell_at(event.x, event.y));
+            self.pond.update_aggregates();
+            self.applied += 1;
+        }
+        while self.applied > 0 && self.events[self.applied - 1].tick > tick {
+            self.applied -= 1;
+            let event = &self.events[self.applied];
+            event.revert(self.pond.cell_at(event.x, event.y));
+            self.pond.update_aggregates();
+        }
+        self.clock = tick;
+    }
+
+    #[inline]
+    pub fn step_forward(&mut self) {
+        let tick = self.clock + 1;
+        self.seek(tick);
+    }
+
+    #[inline]
+    pub fn step_back(&mut self) {
+        if self.clock > 0 {
+            let tick = self.clock - 1;
+            self.seek(tick);
+        }
+    }
+
+    /// Checkpoint of the current state, so a replayed pond can be analyzed
+    /// or resumed.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let current = self.pond.cells().map(|cell| cell.id.0).max().unwrap_or(0);
+        Checkpoint {
+            clock: self.clock,
+            events: self.applied,
+            id_generator: CellIdGenerator::starting_at(current),
+            pond: self.pond.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use config::Config;
+    use disturbance::Disturbances;
+    use events::EventLog;
+    use random_generator::RandomGenerator;
+    use rng::RngKind;
+    use simulation::Simulation;
+    use super::*;
+
+    /// An event log the test can read back while the pond still holds it.
+    #[derive(Clone, Default)]
+    struct SharedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn assert_same_cells(replayed: &CellPond, live: &CellPond, tick: usize) {
+        for (replayed, live) in replayed.cells().zip(live.cells()) {
+            assert!(replayed.id == live.id
+                        && replayed.parent_id == live.parent_id
+                        && replayed.lineage == live.lineage
+                        && replayed.generation == live.generation
+                        && replayed.energy == live.energy
+                        && replayed.genome.0[..] == live.genome.0[..],
+                    "The replayed pond differs at tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn replays_the_live_pond_from_a_checkpoint_taken_at_a_report() {
+        let config = Config { pond_width: 16, pond_height: 12, report_frequency: 100, ..Config::default() };
+        let mut simulation = Simulation::new(config, RandomGenerator::from_seed(RngKind::Xorshift, 7));
+        let start = simulation.pond.clone();
+        let log = SharedLog::default();
+        simulation.pond.record_events(EventLog::new(Box::new(log.clone())).unwrap());
+        // Strikes at the report ticks, so that part of those ticks is in
+        // the checkpoint and part isn't.
+        simulation.set_disturbances(Disturbances::parse("every:100 sweep 0.2").unwrap());
+        let mut checkpoint = Vec::new();
+        let mut middle = None;
+        while simulation.statistics.clock < 300 {
+            simulation.tick(|simulation| if simulation.statistics.clock == 200 {
+                let Simulation { ref pond, ref id_generator, ref statistics, .. } = *simulation;
+                Checkpoint::write(&mut checkpoint, statistics.clock, pond.recorded_events(), id_generator, pond)
+                    .unwrap();
+            });
+            if simulation.statistics.clock == 200 {
+                middle = Some(simulation.pond.clone());
+            }
+        }
+        let events = EventLog::read(&mut &log.0.lock().unwrap()[..]).unwrap();
+        let mut replay = Replay::new(Checkpoint::read(&mut &checkpoint[..]).unwrap(), events);
+        replay.seek(200);
+        assert_same_cells(replay.pond(), middle.as_ref().unwrap(), 200);
+        replay.seek(300);
+        assert_same_cells(replay.pond(), &simulation.pond, 300);
+        replay.seek(0);
+        assert_same_cells(replay.pond(), &start, 0);
+        replay.seek(200);
+        assert_same_cells(replay.pond(), middle.as_ref().unwrap(), 200);
+    }
+}
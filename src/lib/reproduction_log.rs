@@ -0,0 +1,143 @@
+use std::io::{self, Write};
+
+const MAGIC: &[u8; 4] = b"RPRL";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Ndjson,
+    Binary,
+}
+
+pub struct ReproductionRecord {
+    pub tick: usize,
+    pub parent: usize,
+    pub child: usize,
+    pub x: usize,
+    pub y: usize,
+    pub generation: usize,
+    pub parent_genome: u64,
+    pub child_genome: u64,
+    pub hamming_distance: usize,
+}
+
+/// Log of every reproduction. The NDJSON format writes one object per line.
+/// The binary format starts with the `RPRL` magic and a little endian u32
+/// version, followed by records of nine little endian u64 in the order of
+/// the fields of `ReproductionRecord`.
+///
+/// Records are written as cells reproduce, so a write error is kept rather
+/// than returned: the first one is returned by the next `flush`, and the
+/// records are dropped until then.
+pub struct ReproductionLog {
+    format: LogFormat,
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl ReproductionLog {
+    pub fn new(mut writer: Box<dyn Write + Send>, format: LogFormat) -> io::Result<ReproductionLog> {
+        if format == LogFormat::Binary {
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+        }
+        Ok(ReproductionLog { format, writer, error: None })
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, record: &ReproductionRecord) {
+        if self.error.is_none() {
+            self.error = self.write(record).err();
+        }
+    }
+
+    fn write(&mut self, record: &ReproductionRecord) -> io::Result<()> {
+        match self.format {
+            LogFormat::Ndjson => writeln!(
+                self.writer,
+                "{{\"tick\":{},\"parent\":{},\"child\":{},\"x\":{},\"y\":{},\"generation\":{},\
+                 \"parent_genome\":\"{:016x}\",\"child_genome\":\"{:016x}\",\"hamming_distance\":{}}}",
+                record.tick,
+                record.parent,
+                record.child,
+                record.x,
+                record.y,
+                record.generation,
+                record.parent_genome,
+                record.child_genome,
+                record.hamming_distance),
+            LogFormat::Binary => {
+                for value in [
+                    record.tick as u64,
+                    record.parent as u64,
+                    record.child as u64,
+                    record.x as u64,
+                    record.y as u64,
+                    record.generation as u64,
+                    record.parent_genome,
+                    record.child_genome,
+                    record.hamming_distance as u64,
+                ].iter() {
+                    self.writer.write_all(&value.to_le_bytes())?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Takes `capacity` bytes, then fails.
+    struct Full(usize);
+
+    impl Write for Full {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "Full"));
+            }
+            let written = buffer.len().min(self.0);
+            self.0 -= written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(tick: usize) -> ReproductionRecord {
+        ReproductionRecord {
+            tick,
+            parent: 1,
+            child: 2,
+            x: 3,
+            y: 4,
+            generation: 5,
+            parent_genome: 6,
+            child_genome: 7,
+            hamming_distance: 8,
+        }
+    }
+
+    #[test]
+    fn keeps_the_first_write_error_for_flush() {
+        // The header and the first record fit, 8 + 72 bytes.
+        let mut log = ReproductionLog::new(Box::new(Full(100)), LogFormat::Binary).unwrap();
+        log.record(&record(1));
+        assert!(log.flush().is_ok());
+        log.record(&record(2));
+        log.record(&record(3));
+        assert_eq!(log.flush().map_err(|error| error.kind()), Err(io::ErrorKind::WriteZero));
+        assert!(log.flush().is_ok());
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use cell::CellPosition;
+
+/// A source of random 64-bit words, the same on every target.
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+
+    #[inline]
+    fn generate_integer(&mut self) -> u64 {
+        self.next_u64()
+    }
+
+    #[inline]
+    fn generate_boolean(&mut self) -> bool {
+        (self.next_u64() & 0x80) > 0
+    }
+
+    /// Uniform in `[0, 1)`.
+    #[inline]
+    fn generate_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Number of failed trials before the first success, each trial
+    /// succeeding with probability `p`.
+    #[inline]
+    fn generate_geometric(&mut self, p: f64) -> usize {
+        if p >= 1.0 {
+            0
+        } else if p <= 0.0 {
+            usize::MAX
+        } else {
+            ((1.0 - self.generate_float()).ln() / (1.0 - p).ln()).floor() as usize
+        }
+    }
+
+    /// Uniform in `[0, bound)`, by Lemire's multiply and reject.
+    #[inline]
+    fn generate_below(&mut self, bound: u64) -> u64 {
+        let mut product = u128::from(self.next_u64()) * u128::from(bound);
+        if (product as u64) < bound {
+            let threshold = bound.wrapping_neg() % bound;
+            while (product as u64) < threshold {
+                product = u128::from(self.next_u64()) * u128::from(bound);
+            }
+        }
+        (product >> 64) as u64
+    }
+
+    #[inline]
+    fn generate_cell_position(&mut self, width: usize, height: usize) -> CellPosition {
+        let index = self.generate_below((width * height) as u64) as usize;
+        CellPosition(index % width, index / width)
+    }
+}
+
+/// Expands a single seed into as many well mixed words as a generator needs.
+#[derive(Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    #[inline]
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64(seed)
+    }
+
+    /// Advances by 2^60 draws, a sixteenth of the period.
+    #[inline]
+    pub fn jump(&mut self) {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15u64.wrapping_shl(60));
+    }
+}
+
+impl Rng for SplitMix64 {
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// The generator the pond always used.
+#[derive(Clone)]
+pub struct Xorshift128Plus([u64; 2]);
+
+#[inline]
+fn xorshift_step(state: [u64; 2]) -> [u64; 2] {
+    let mut x = state[0];
+    let y = state[1];
+    x ^= x << 23;
+    [y, x ^ y ^ (x >> 17) ^ (y >> 26)]
+}
+
+/// A linear map on the 128 bits of a xorshift state, as its columns.
+type BitMatrix = [u128; 128];
+
+#[inline]
+fn apply(matrix: &BitMatrix, vector: u128) -> u128 {
+    (0..128).filter(|bit| vector >> bit & 1 == 1).fold(0, |result, bit| result ^ matrix[bit])
+}
+
+#[inline]
+fn to_bits(state: [u64; 2]) -> u128 {
+    u128::from(state[0]) | (u128::from(state[1]) << 64)
+}
+
+#[inline]
+fn from_bits(bits: u128) -> [u64; 2] {
+    [bits as u64, (bits >> 64) as u64]
+}
+
+/// The state transition of a single draw.
+fn xorshift_step_matrix() -> BitMatrix {
+    let mut matrix = [0; 128];
+    for (bit, column) in matrix.iter_mut().enumerate() {
+        *column = to_bits(xorshift_step(from_bits(1 << bit)));
+    }
+    matrix
+}
+
+/// The transition of twice as many draws as `matrix`.
+fn square(matrix: &BitMatrix) -> BitMatrix {
+    let mut square = [0; 128];
+    for (column, squared) in matrix.iter().zip(square.iter_mut()) {
+        *squared = apply(matrix, *column);
+    }
+    square
+}
+
+/// The state transition of 2^64 draws, found by squaring the one of a
+/// single draw 64 times.
+fn xorshift_jump_matrix() -> &'static BitMatrix {
+    static JUMP: OnceLock<BitMatrix> = OnceLock::new();
+    JUMP.get_or_init(|| (0..64).fold(xorshift_step_matrix(), |matrix, _| square(&matrix)))
+}
+
+impl Xorshift128Plus {
+    #[inline]
+    pub fn new(first: u64, second: u64) -> Xorshift128Plus {
+        Xorshift128Plus([first, second])
+    }
+
+    pub fn from_seed(seed: u64) -> Xorshift128Plus {
+        let mut seeder = SplitMix64::new(seed);
+        Xorshift128Plus::new(seeder.next_u64(), seeder.next_u64())
+    }
+
+    /// Advances by 2^64 draws.
+    pub fn jump(&mut self) {
+        self.0 = from_bits(apply(xorshift_jump_matrix(), to_bits(self.0)));
+    }
+}
+
+impl Rng for Xorshift128Plus {
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.0 = xorshift_step(self.0);
+        self.0[1].wrapping_add(self.0[0])
+    }
+}
+
+const PCG_MULTIPLIER: u128 = 0x2360_ed05_1fc6_5da4_4385_df64_9fcc_f645;
+
+/// PCG XSL RR 128/64.
+#[derive(Clone)]
+pub struct Pcg64 {
+    state: u128,
+    increment: u128,
+}
+
+impl Pcg64 {
+    pub fn from_seed(seed: u64) -> Pcg64 {
+        let mut seeder = SplitMix64::new(seed);
+        let mut word = || u128::from(seeder.next_u64());
+        let state = (word() << 64) | word();
+        let increment = (word() << 64) | word();
+        Pcg64::new(state, increment)
+    }
+
+    /// Seeded like the reference `pcg64_srandom_r`, except that the
+    /// increment is given rather than the stream it is derived from.
+    fn new(state: u128, increment: u128) -> Pcg64 {
+        let mut pcg = Pcg64 { state: 0, increment: increment | 1 };
+        pcg.step();
+        pcg.state = pcg.state.wrapping_add(state);
+        pcg.step();
+        pcg
+    }
+
+    #[inline]
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.increment);
+    }
+
+    /// Advances by 2^64 draws.
+    pub fn jump(&mut self) {
+        self.advance(1 << 64);
+    }
+
+    /// Advances by `delta` draws in logarithmic time, composing the LCG
+    /// step with itself.
+    fn advance(&mut self, mut delta: u128) {
+        let (mut multiplier, mut increment) = (PCG_MULTIPLIER, self.increment);
+        let (mut total_multiplier, mut total_increment) = (1u128, 0u128);
+        while delta > 0 {
+            if delta & 1 == 1 {
+                total_multiplier = total_multiplier.wrapping_mul(multiplier);
+                total_increment = total_increment.wrapping_mul(multiplier).wrapping_add(increment);
+            }
+            increment = multiplier.wrapping_add(1).wrapping_mul(increment);
+            multiplier = multiplier.wrapping_mul(multiplier);
+            delta >>= 1;
+        }
+        self.state = total_multiplier.wrapping_mul(self.state).wrapping_add(total_increment);
+    }
+}
+
+impl Rng for Pcg64 {
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.step();
+        let rotation = (self.state >> 122) as u32;
+        (((self.state >> 64) as u64) ^ (self.state as u64)).rotate_right(rotation)
+    }
+}
+
+const CHACHA_ROUNDS: usize = 20;
+
+/// The ChaCha20 keystream, keyed from the seed, used as a generator.
+#[derive(Clone)]
+pub struct ChaCha {
+    input: [u32; 16],
+    output: [u32; 16],
+    index: usize,
+}
+
+impl ChaCha {
+    pub fn from_seed(seed: u64) -> ChaCha {
+        let mut seeder = SplitMix64::new(seed);
+        let mut input = [0; 16];
+        input[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+        for key in input[4..12].chunks_mut(2) {
+            let word = seeder.next_u64();
+            key[0] = word as u32;
+            key[1] = (word >> 32) as u32;
+        }
+        ChaCha { input, output: [0; 16], index: 16 }
+    }
+
+    /// Moves to the start of the next stream of the same key, 2^64 blocks
+    /// long.
+    pub fn jump(&mut self) {
+        self.input[12] = 0;
+        self.input[13] = 0;
+        self.input[14] = self.input[14].wrapping_add(1);
+        if self.input[14] == 0 {
+            self.input[15] = self.input[15].wrapping_add(1);
+        }
+        self.index = 16;
+    }
+
+    fn refill(&mut self) {
+        let mut x = self.input;
+        for _ in 0..CHACHA_ROUNDS / 2 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+        for (output, (x, input)) in self.output.iter_mut().zip(x.iter().zip(self.input.iter())) {
+            *output = x.wrapping_add(*input);
+        }
+        // 64-bit block counter.
+        self.input[12] = self.input[12].wrapping_add(1);
+        if self.input[12] == 0 {
+            self.input[13] = self.input[13].wrapping_add(1);
+        }
+        self.index = 0;
+    }
+}
+
+#[inline]
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+impl Rng for ChaCha {
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        if self.index >= 16 {
+            self.refill();
+        }
+        let word = u64::from(self.output[self.index]) | (u64::from(self.output[self.index + 1]) << 32);
+        self.index += 2;
+        word
+    }
+}
+
+/// The generators a run can pick at run time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RngKind {
+    Xorshift,
+    Pcg,
+    SplitMix,
+    ChaCha,
+}
+
+impl fmt::Display for RngKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            RngKind::Xorshift => write!(f, "xorshift"),
+            RngKind::Pcg => write!(f, "pcg"),
+            RngKind::SplitMix => write!(f, "splitmix"),
+            RngKind::ChaCha => write!(f, "chacha"),
+        }
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<RngKind, String> {
+        match name {
+            "xorshift" => Ok(RngKind::Xorshift),
+            "pcg" => Ok(RngKind::Pcg),
+            "splitmix" => Ok(RngKind::SplitMix),
+            "chacha" => Ok(RngKind::ChaCha),
+            _ => Err(format!("Unknown generator {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws<R: Rng>(rng: &mut R, count: usize) -> Vec<u64> {
+        (0..count).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn splitmix64_matches_the_reference() {
+        assert_eq!(draws(&mut SplitMix64::new(0), 3),
+                   vec![0xe220a8397b1dcdaf, 0x6e789e6aa1b965f4, 0x06c45d188009454f]);
+    }
+
+    #[test]
+    fn pcg64_matches_the_reference() {
+        // pcg64_srandom_r(&rng, 42, 54) in the PCG C library.
+        let mut pcg = Pcg64::new(42, 54 << 1);
+        assert_eq!(draws(&mut pcg, 6), vec![
+            0x86b1da1d72062b68, 0x1304aa46c9853d39, 0xa3670e9e0dd50358,
+            0xf9090e529a7dae00, 0xc85b9fd837996f2c, 0x606121f8e3919196,
+        ]);
+    }
+
+    #[test]
+    fn pcg64_advance_matches_stepping() {
+        for delta in [0, 1, 2, 3, 17, 100] {
+            let mut advanced = Pcg64::from_seed(delta as u64);
+            let mut stepped = advanced.clone();
+            advanced.advance(delta);
+            for _ in 0..delta {
+                stepped.step();
+            }
+            assert_eq!(advanced.state, stepped.state, "advance({})", delta);
+        }
+    }
+
+    #[test]
+    fn xorshift_step_matrix_matches_a_step() {
+        let matrix = xorshift_step_matrix();
+        let mut seeder = SplitMix64::new(1);
+        for _ in 0..20 {
+            let state = [seeder.next_u64(), seeder.next_u64()];
+            assert_eq!(from_bits(apply(&matrix, to_bits(state))), xorshift_step(state));
+        }
+    }
+
+    #[test]
+    fn squared_xorshift_matrix_matches_stepping() {
+        // Five squarings make 32 draws.
+        let matrix = (0..5).fold(xorshift_step_matrix(), |matrix, _| square(&matrix));
+        let mut stepped = Xorshift128Plus::from_seed(3);
+        let state = stepped.0;
+        for _ in 0..32 {
+            stepped.next_u64();
+        }
+        assert_eq!(from_bits(apply(&matrix, to_bits(state))), stepped.0);
+    }
+
+    #[test]
+    fn chacha_quarter_round_matches_rfc_8439() {
+        // Section 2.1.1.
+        let mut x = [0; 16];
+        x[..4].copy_from_slice(&[0x11111111, 0x01020304, 0x9b8d6f43, 0x01234567]);
+        quarter_round(&mut x, 0, 1, 2, 3);
+        assert_eq!(x[..4], [0xea2a92f4, 0xcb1cf8ce, 0x4581472e, 0x5881c4bb]);
+    }
+
+    #[test]
+    fn chacha_block_matches_rfc_8439() {
+        // Section 2.3.2: the key 00 01 .. 1f, block 1 and the nonce
+        // 00 00 00 09 00 00 00 4a 00 00 00 00, its first word standing in
+        // for the high half of the block counter.
+        let mut chacha = ChaCha {
+            input: [
+                0x61707865, 0x3320646e, 0x79622d32, 0x6b206574,
+                0x03020100, 0x07060504, 0x0b0a0908, 0x0f0e0d0c,
+                0x13121110, 0x17161514, 0x1b1a1918, 0x1f1e1d1c,
+                0x00000001, 0x09000000, 0x4a000000, 0x00000000,
+            ],
+            output: [0; 16],
+            index: 16,
+        };
+        assert_eq!(chacha.next_u64(), 0x15593bd1e4e7f110);
+        assert_eq!(chacha.output, [
+            0xe4e7f110, 0x15593bd1, 0x1fdd0f50, 0xc47120a3,
+            0xc7f4d1c7, 0x0368c033, 0x9aaa2204, 0x4e6cd4c3,
+            0x466482d2, 0x09aa9f07, 0x05d7c214, 0xa2028bd9,
+            0xd19c12b5, 0xb94e16de, 0xe883d0cb, 0x4e3c50a2,
+        ]);
+        assert_eq!(chacha.input[12..14], [2, 0x09000000]);
+    }
+}
@@ -0,0 +1,283 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// How often the value of a `Schedule::Sine` is brought up to date, per
+/// period.
+const SINE_STEPS: usize = 360;
+
+/// The parameters a schedule can drive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameter {
+    InflowRate,
+    InflowFrequency,
+    MutationRate,
+}
+
+impl FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Parameter, String> {
+        match name {
+            "inflow_rate" => Ok(Parameter::InflowRate),
+            "inflow_frequency" => Ok(Parameter::InflowFrequency),
+            "mutation_rate" => Ok(Parameter::MutationRate),
+            _ => Err(format!("Unknown scheduled parameter {}", name)),
+        }
+    }
+}
+
+/// The value of a parameter over time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    /// `(tick, value)` pairs in tick order, each value holding from its
+    /// tick on. Before the first one the parameter keeps its base value.
+    Changes(Vec<(usize, usize)>),
+    /// `(length, value)` pairs, each value holding for its length in
+    /// ticks, repeated in turn from tick 0.
+    Phases(Vec<(usize, usize)>),
+    /// `mean + amplitude * sin(2 pi tick / period)`.
+    Sine { mean: usize, amplitude: usize, period: usize },
+}
+
+impl Schedule {
+    pub fn value(&self, tick: usize, base: usize) -> usize {
+        match self {
+            Schedule::Changes(changes) => changes.iter()
+                .take_while(|(start, _)| *start <= tick)
+                .last()
+                .map_or(base, |(_, value)| *value),
+            Schedule::Phases(phases) => {
+                let mut offset = tick % phases.iter().map(|(length, _)| length).sum::<usize>();
+                for (length, value) in phases {
+                    if offset < *length {
+                        return *value;
+                    }
+                    offset -= length;
+                }
+                unreachable!("The offset is within a cycle")
+            },
+            Schedule::Sine { mean, amplitude, period } => {
+                let phase = 2.0 * PI * (tick % period) as f64 / *period as f64;
+                (*mean as f64 + *amplitude as f64 * phase.sin()).round().max(0.0) as usize
+            },
+        }
+    }
+
+    /// The next tick after `tick` where the value may change.
+    pub fn next_change(&self, tick: usize) -> usize {
+        match self {
+            Schedule::Changes(changes) => changes.iter()
+                .map(|(start, _)| *start)
+                .find(|start| *start > tick)
+                .unwrap_or(usize::MAX),
+            Schedule::Phases(phases) => {
+                let cycle = phases.iter().map(|(length, _)| length).sum::<usize>();
+                let start = tick - tick % cycle;
+                phases.iter()
+                    .scan(start, |end, (length, _)| {
+                        *end += length;
+                        Some(*end)
+                    })
+                    .find(|end| *end > tick)
+                    .expect("A cycle ends after its start")
+            },
+            Schedule::Sine { period, .. } => tick + (period / SINE_STEPS).max(1),
+        }
+    }
+}
+
+/// `changes:<tick>=<value>/...`, `phases:<length>=<value>/...` or
+/// `sine:<mean>:<amplitude>:<period>`, without commas so that sweeps can
+/// list several.
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String> {
+        let invalid = || format!("Invalid schedule {}", s);
+        let number = |text: &str| text.trim().parse::<usize>().map_err(|_| invalid());
+        let pairs = |text: &str| text.split('/')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => Ok((number(key)?, number(value)?)),
+                None => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let schedule = match s.trim().split_once(':') {
+            Some(("changes", changes)) => {
+                let changes = pairs(changes)?;
+                if changes.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    return Err(format!("Changes out of tick order in {}", s));
+                }
+                Schedule::Changes(changes)
+            },
+            Some(("phases", phases)) => {
+                let phases = pairs(phases)?;
+                if phases.iter().any(|(length, _)| *length == 0) {
+                    return Err(format!("Empty phase in {}", s));
+                }
+                Schedule::Phases(phases)
+            },
+            Some(("sine", sine)) => {
+                let numbers = sine.split(':').map(number).collect::<Result<Vec<_>, _>>()?;
+                match numbers.as_slice() {
+                    [mean, amplitude, period] if *period > 0 =>
+                        Schedule::Sine { mean: *mean, amplitude: *amplitude, period: *period },
+                    _ => return Err(invalid()),
+                }
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(schedule)
+    }
+}
+
+/// The schedules of a run, if any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schedules {
+    pub inflow_rate: Option<Schedule>,
+    pub inflow_frequency: Option<Schedule>,
+    pub mutation_rate: Option<Schedule>,
+}
+
+impl Schedules {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inflow_rate.is_none() && self.inflow_frequency.is_none() && self.mutation_rate.is_none()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, parameter: Parameter) -> &mut Option<Schedule> {
+        match parameter {
+            Parameter::InflowRate => &mut self.inflow_rate,
+            Parameter::InflowFrequency => &mut self.inflow_frequency,
+            Parameter::MutationRate => &mut self.mutation_rate,
+        }
+    }
+
+    /// Reads a list of changes, one `<tick> <parameter> <value>` per line.
+    /// Lines starting with `#` are ignored.
+    pub fn parse_changes(&mut self, text: &str) -> Result<(), String> {
+        let mut changes: Vec<(Parameter, usize, usize)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid change on line {}", number + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return Err(invalid());
+            }
+            let tick = fields[0].parse().map_err(|_| invalid())?;
+            let parameter = fields[1].parse()?;
+            let value = fields[2].parse().map_err(|_| invalid())?;
+            changes.push((parameter, tick, value));
+        }
+        changes.sort_by_key(|(_, tick, _)| *tick);
+        for parameter in [Parameter::InflowRate, Parameter::InflowFrequency, Parameter::MutationRate].iter() {
+            let mut list: Vec<(usize, usize)> = Vec::new();
+            for (_, tick, value) in changes.iter().filter(|(changed, _, _)| changed == parameter) {
+                match list.last_mut() {
+                    Some(last) if last.0 == *tick => last.1 = *value,
+                    _ => list.push((*tick, *value)),
+                }
+            }
+            if !list.is_empty() {
+                *self.get_mut(*parameter) = Some(Schedule::Changes(list));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The values of the scheduled parameters at some tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub inflow_rate: usize,
+    pub inflow_frequency: usize,
+    pub mutation_rate: usize,
+}
+
+impl fmt::Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{},{},{}", self.inflow_rate, self.inflow_frequency, self.mutation_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(text: &str) -> Schedule {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_every_kind() {
+        assert_eq!(schedule("changes:0=5/100=7"), Schedule::Changes(vec![(0, 5), (100, 7)]));
+        assert_eq!(schedule("phases:10=1/5=2"), Schedule::Phases(vec![(10, 1), (5, 2)]));
+        assert_eq!(schedule(" sine:100:50:360 "), Schedule::Sine { mean: 100, amplitude: 50, period: 360 });
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        for text in ["changes:5=1/5=2", "changes:5=1/2=2", "changes:5", "changes:a=1", "phases:0=1",
+                     "sine:1:2:0", "sine:1:2", "ramp:1=2", "changes"].iter() {
+            assert!(text.parse::<Schedule>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn changes_hold_from_their_tick() {
+        let changes = schedule("changes:10=5/100=7");
+        assert_eq!([0, 9, 10, 99, 100, 1000].iter().map(|tick| changes.value(*tick, 3)).collect::<Vec<_>>(),
+                   vec![3, 3, 5, 5, 7, 7]);
+        assert_eq!(changes.next_change(0), 10);
+        assert_eq!(changes.next_change(10), 100);
+        assert_eq!(changes.next_change(99), 100);
+        assert_eq!(changes.next_change(100), usize::MAX);
+    }
+
+    #[test]
+    fn phases_wrap_around() {
+        let phases = schedule("phases:10=1/5=2");
+        assert_eq!([0, 9, 10, 14, 15, 24, 25, 29].iter().map(|tick| phases.value(*tick, 0)).collect::<Vec<_>>(),
+                   vec![1, 1, 2, 2, 1, 1, 2, 2]);
+        assert_eq!([0, 9, 10, 14, 15, 29].iter().map(|tick| phases.next_change(*tick)).collect::<Vec<_>>(),
+                   vec![10, 10, 15, 15, 25, 30]);
+    }
+
+    #[test]
+    fn sine_stays_non_negative() {
+        let sine = schedule("sine:100:50:360");
+        assert_eq!([0, 90, 180, 270, 360].iter().map(|tick| sine.value(*tick, 0)).collect::<Vec<_>>(),
+                   vec![100, 150, 100, 50, 100]);
+        assert_eq!(schedule("sine:10:50:4").value(3, 0), 0);
+        assert_eq!(sine.next_change(7), 8);
+        assert_eq!(schedule("sine:0:1:7200").next_change(7), 27);
+    }
+
+    #[test]
+    fn parse_changes_keeps_the_last_change_of_a_tick() {
+        let mut schedules = Schedules::default();
+        schedules.parse_changes("# tick parameter value\n\
+                                 500 inflow_rate 10\n\
+                                 100 inflow_rate 20\n\
+                                 \n\
+                                 100 mutation_rate 3\n\
+                                 100 inflow_rate 30\n").unwrap();
+        assert_eq!(schedules, Schedules {
+            inflow_rate: Some(Schedule::Changes(vec![(100, 30), (500, 10)])),
+            inflow_frequency: None,
+            mutation_rate: Some(Schedule::Changes(vec![(100, 3)])),
+        });
+    }
+
+    #[test]
+    fn parse_changes_rejects_invalid_lines() {
+        let mut schedules = Schedules::default();
+        assert_eq!(schedules.parse_changes("1 inflow_rate 2\n1 inflow_rate"),
+                   Err("Invalid change on line 2".to_string()));
+        assert_eq!(schedules.parse_changes("1 speed 2"), Err("Unknown scheduled parameter speed".to_string()));
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt;
+use std::sync::Arc;
+use cell::{CellIdGenerator, CellPosition};
+use cell_pond::CellPond;
+use cell_vm::CellVM;
+use config::{Config, Scheduling};
+use disturbance::{Disturbances, MutationBoost, Strike};
+use events::EventKind;
+use genome::Genome;
+use genome_bank::GenomeBank;
+use genome_store::GenomeBuffer;
+use inflow::InflowStrategy;
+use mask::Mask;
+use random_generator::{RandomGenerator, RandomStreams};
+use rng::Rng;
+use schedule::{Parameters, Schedule};
+use statistics::Statistics;
+
+/// The parts of a tick other than the execution of a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Report,
+    Inflow,
+}
+
+/// Called around the phases of a tick, for instance to time them. The
+/// hooks do nothing by default, and `()` has no others.
+pub trait TickHooks {
+    #[inline]
+    fn enter(&mut self, _phase: Phase) {}
+
+    #[inline]
+    fn leave(&mut self, _phase: Phase) {}
+}
+
+impl TickHooks for () {}
+
+/// A pond together with everything needed to run it.
+pub struct Simulation<G: GenomeBuffer = Genome> {
+    pub config: Config,
+    pub pond: CellPond<G>,
+    pub id_generator: CellIdGenerator,
+    pub random: RandomStreams,
+    pub statistics: Statistics,
+    skipped_ticks: usize,
+    inflow_strategy: Box<dyn InflowStrategy>,
+    /// Where the inflow draws its genomes from, if not at random.
+    genome_bank: Option<GenomeBank>,
+    inflow_frequency: usize,
+    /// The tick where the scheduled parameters next need updating.
+    next_schedule_update: usize,
+    disturbances: Option<Disturbances>,
+    /// The tick of the next disturbance or end of a mutation boost.
+    next_disturbance: usize,
+}
+
+impl Simulation {
+    /// A new pond, its random decisions drawn from streams split off
+    /// `random_generator`, see `RandomStreams`.
+    pub fn new(config: Config, random_generator: RandomGenerator) -> Simulation {
+        Simulation::with_buffers(config, random_generator)
+    }
+}
+
+impl Simulation<Arc<Genome>> {
+    /// `new`, with cells born with the same genome sharing one buffer, see
+    /// `CellPond::share_genomes`.
+    pub fn sharing_genomes(config: Config, random_generator: RandomGenerator) -> Simulation<Arc<Genome>> {
+        let mut simulation = Simulation::with_buffers(config, random_generator);
+        simulation.pond.share_genomes();
+        simulation
+    }
+}
+
+impl<G: GenomeBuffer> Simulation<G> {
+    fn with_buffers(config: Config, random_generator: RandomGenerator) -> Simulation<G> {
+        let mut random = RandomStreams::new(random_generator);
+        let mut id_generator = CellIdGenerator::new();
+        let mut pond = CellPond::with_config(&config, &mut id_generator, &mut random.inflow);
+        if config.scheduling == Scheduling::Active {
+            pond.index_active_cells();
+        }
+        let inflow_strategy = config.inflow.strategy(config.pond_width, config.pond_height, &mut random.inflow);
+        let mut simulation = Simulation {
+            config,
+            pond,
+            id_generator,
+            random,
+            statistics: Statistics::new(),
+            skipped_ticks: 0,
+            inflow_strategy,
+            genome_bank: None,
+            inflow_frequency: 0,
+            next_schedule_update: 0,
+            disturbances: None,
+            next_disturbance: usize::MAX,
+        };
+        simulation.update_parameters();
+        simulation.skip_ticks();
+        simulation
+    }
+
+    /// Advances the clock by one tick: reports if one is due, lets a random
+    /// genome flow in if one is due and executes a random cell.
+    #[inline]
+    pub fn tick<F: FnMut(&mut Simulation<G>)>(&mut self, report: F) {
+        self.tick_with(report, &mut ());
+    }
+
+    /// `tick`, calling `hooks` around its phases.
+    #[inline]
+    pub fn tick_with<F: FnMut(&mut Simulation<G>), H: TickHooks>(&mut self, mut report: F, hooks: &mut H) {
+        self.statistics.clock += 1;
+        self.follow_schedules();
+        self.follow_disturbances();
+        if self.report_due() {
+            hooks.enter(Phase::Report);
+            report(self);
+            hooks.leave(Phase::Report);
+        }
+        if self.inflow_due() {
+            hooks.enter(Phase::Inflow);
+            self.scheduled_inflow();
+            hooks.leave(Phase::Inflow);
+        }
+        self.scheduled_execution();
+    }
+
+    #[inline]
+    fn report_due(&self) -> bool {
+        self.statistics.clock.is_multiple_of(self.config.report_frequency)
+    }
+
+    #[inline]
+    fn inflow_due(&self) -> bool {
+        self.statistics.clock.is_multiple_of(self.inflow_frequency)
+    }
+
+    /// Brings the scheduled parameters up to date with the clock.
+    #[inline]
+    pub fn follow_schedules(&mut self) {
+        if self.statistics.clock >= self.next_schedule_update {
+            self.update_parameters();
+        }
+    }
+
+    fn update_parameters(&mut self) {
+        let (clock, config) = (self.statistics.clock, &self.config);
+        let mut next = usize::MAX;
+        let mut follow = |schedule: &Option<Schedule>, base: usize| match schedule {
+            Some(schedule) => {
+                next = next.min(schedule.next_change(clock));
+                schedule.value(clock, base)
+            },
+            None => base,
+        };
+        let inflow_rate = follow(&config.schedules.inflow_rate, config.inflow_rate_base);
+        let inflow_frequency = follow(&config.schedules.inflow_frequency, config.inflow_frequency);
+        let mutation_rate = follow(&config.schedules.mutation_rate, config.mutation_rate);
+        self.pond.set_inflow_rate(inflow_rate);
+        self.pond.set_mutation_rate(mutation_rate);
+        self.inflow_frequency = inflow_frequency.max(1);
+        self.next_schedule_update = next;
+    }
+
+    /// Disturbs the pond from now on as `disturbances` says, see
+    /// `Disturbances`.
+    pub fn set_disturbances(&mut self, mut disturbances: Disturbances) {
+        disturbances.start(self.statistics.clock, &mut self.random.disturbance);
+        self.next_disturbance = disturbances.next_strike();
+        self.disturbances = Some(disturbances);
+    }
+
+    /// Strikes the disturbances due and ends the mutation boosts over.
+    #[inline]
+    pub fn follow_disturbances(&mut self) {
+        if self.statistics.clock >= self.next_disturbance {
+            self.disturb();
+        }
+    }
+
+    fn disturb(&mut self) {
+        let clock = self.statistics.clock;
+        self.pond.expire_boosts(clock);
+        let mut disturbances = self.disturbances.take().expect("Disturbances are set");
+        let active = self.pond.indexed_active_cells();
+        let (width, height) = (self.pond.width(), self.pond.height());
+        for strike in disturbances.strike(clock, width, height, &mut self.random.disturbance) {
+            let cells = self.apply(&strike);
+            disturbances.log(clock, &strike, cells).expect("Can't write disturbance log");
+        }
+        self.pond.commit(clock, EventKind::Disturbance).expect("Can't write events");
+        // The skip only depends on the number of cells with energy, so it
+        // is redrawn only if a strike changed that number, leaving the
+        // selection stream alone otherwise.
+        if self.pond.indexed_active_cells() != active {
+            self.skip_ticks();
+        }
+        self.next_disturbance = self.pond.boosts().iter()
+            .map(|boost| boost.until)
+            .fold(disturbances.next_strike(), usize::min);
+        self.disturbances = Some(disturbances);
+    }
+
+    /// Applies `strike` and returns the number of cells it reached.
+    fn apply(&mut self, strike: &Strike) -> usize {
+        let (width, height) = (self.pond.width(), self.pond.height());
+        let cells = match *strike {
+            Strike::Wipe(area) | Strike::MutationBoost { area, .. } => area.cells(width, height),
+            Strike::Sweep(fraction) => (0..width * height)
+                .map(|index| CellPosition(index % width, index / width))
+                .filter(|_| self.random.disturbance.generate_float() < fraction)
+                .collect(),
+        };
+        let cells: Vec<CellPosition> = cells.into_iter()
+            .filter(|position| !self.pond.is_obstacle(position))
+            .collect();
+        if let Strike::MutationBoost { area, rate, until } = *strike {
+            self.pond.boost_mutation(MutationBoost { area, rate, until });
+        } else {
+            self.pond.wipe(&cells, &mut self.id_generator, &G::new(Genome::new()));
+        }
+        cells.len()
+    }
+
+    /// The current values of the parameters schedules can drive.
+    #[inline]
+    pub fn parameters(&self) -> Parameters {
+        Parameters {
+            inflow_rate: self.pond.inflow_rate(),
+            inflow_frequency: self.inflow_frequency,
+            mutation_rate: self.pond.mutation_rate(),
+        }
+    }
+
+    #[inline]
+    fn scheduled_inflow(&mut self) {
+        self.inflow();
+        self.skip_ticks();
+    }
+
+    #[inline]
+    fn scheduled_execution(&mut self) {
+        match self.config.scheduling {
+            Scheduling::Uniform => self.execute(),
+            Scheduling::Active if self.skipped_ticks > 0 => {
+                // Uniform scheduling would have picked a cell without
+                // energy, which does nothing.
+                self.skipped_ticks -= 1;
+                self.statistics.cell_executions += 1;
+            },
+            Scheduling::Active => {
+                let active = self.pond.indexed_active_cells().expect("Active cells aren't indexed");
+                let slot = self.random.selection.generate_below(active as u64) as usize;
+                let position = self.pond.active_cell_position(slot);
+                self.execute_at(position);
+                self.skip_ticks();
+            },
+        }
+    }
+
+    /// Draws how many ticks pass before uniform scheduling would pick a
+    /// cell with energy. Since only inflows, executions and disturbances
+    /// change which cells have energy, the draw stays exact until the next
+    /// of them.
+    fn skip_ticks(&mut self) {
+        if let Some(active) = self.pond.indexed_active_cells() {
+            let area = self.pond.width() * self.pond.height();
+            self.skipped_ticks = self.random.selection.generate_geometric(active as f64 / area as f64);
+        }
+    }
+
+    /// See `CellPond::place_obstacles`.
+    pub fn place_obstacles(&mut self, mask: &Mask) -> Result<(), String> {
+        self.pond.place_obstacles(mask)?;
+        self.skip_ticks();
+        Ok(())
+    }
+
+    /// See `CellPond::set_energy`.
+    pub fn set_energy(&mut self, mask: &Mask) -> Result<(), String> {
+        self.pond.set_energy(mask)?;
+        self.skip_ticks();
+        Ok(())
+    }
+
+    /// See `CellPond::place_genomes`.
+    pub fn place_genomes(&mut self, mask: &Mask, genomes: &[Genome]) -> Result<(), String> {
+        self.pond.place_genomes(mask, genomes, &mut self.id_generator)
+    }
+
+    /// Replaces the strategy picked by the configuration.
+    #[inline]
+    pub fn set_inflow_strategy(&mut self, strategy: Box<dyn InflowStrategy>) {
+        self.inflow_strategy = strategy;
+    }
+
+    /// Draws the inflow genomes from `bank` from now on.
+    #[inline]
+    pub fn set_genome_bank(&mut self, bank: GenomeBank) {
+        self.genome_bank = Some(bank);
+    }
+
+    #[inline]
+    pub fn inflow(&mut self) {
+        let position = self.inflow_strategy.position(&mut self.random.inflow);
+        let genome = match self.genome_bank {
+            Some(ref bank) => bank.draw(&mut self.random.inflow),
+            None => Genome::random(&mut self.random.inflow),
+        };
+        if !self.pond.is_obstacle(&position) {
+            self.pond.replace(&position, self.id_generator.next(), genome);
+        }
+        self.pond.commit(self.statistics.clock, EventKind::Inflow).expect("Can't write events");
+    }
+
+    #[inline]
+    pub fn execute(&mut self) {
+        let position = self.random.selection
+            .generate_cell_position(self.pond.width(), self.pond.height());
+        self.execute_at(position);
+    }
+
+    #[inline]
+    fn execute_at(&mut self, position: CellPosition) {
+        CellVM::new(position,
+                    &mut self.pond,
+                    &mut self.id_generator,
+                    &mut self.random,
+                    &mut self.statistics).execute();
+        self.pond.commit(self.statistics.clock, EventKind::Execution).expect("Can't write events");
+    }
+
+    #[inline]
+    pub fn sample(&self) -> Sample {
+        Sample::new(&self.pond, &self.statistics)
+    }
+}
+
+/// The pond totals written at every report.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub clock: usize,
+    pub total_energy: usize,
+    pub active_cells: usize,
+    pub viable_replicators: usize,
+    pub max_generation: usize,
+    pub metabolism: usize,
+}
+
+impl Sample {
+    pub fn new<G: GenomeBuffer>(pond: &CellPond<G>, statistics: &Statistics) -> Sample {
+        Sample {
+            clock: statistics.clock,
+            total_energy: pond.total_energy(),
+            active_cells: pond.total_active_cells(),
+            viable_replicators: pond.total_viable_replicators(),
+            max_generation: pond.max_generation(),
+            metabolism: statistics.metabolism(),
+        }
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{},{},{},{},{}",
+               self.clock,
+               self.total_energy,
+               self.active_cells,
+               self.viable_replicators,
+               self.max_generation)
+    }
+}
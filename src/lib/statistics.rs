@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::ops::{Index, IndexMut};
+use cell::{Cell, CellPosition};
+use genome_store::GenomeBuffer;
 use instruction::Instruction;
+use reproduction_log::ReproductionLog;
 
 pub(crate) struct InstructionCounter {
     zero: usize,
@@ -90,6 +94,300 @@ impl<'a> IndexMut<&'a Instruction> for InstructionCounter {
         }
     }
 }
+/// Counts of consecutive instructions fetched within a single cell
+/// execution. Sequences never span two executions.
+pub struct NgramCounter {
+    bigrams: Vec<usize>,
+    trigrams: Vec<usize>,
+    history: usize,
+    history_length: usize,
+    top: usize,
+}
+
+impl NgramCounter {
+    pub(crate) fn new(top: usize) -> NgramCounter {
+        NgramCounter {
+            bigrams: vec![0; 16 * 16],
+            trigrams: vec![0; 16 * 16 * 16],
+            history: 0,
+            history_length: 0,
+            top,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn start(&mut self) {
+        self.history = 0;
+        self.history_length = 0;
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, instruction: Instruction) {
+        self.history = ((self.history << 4) | instruction as usize) & 0xfff;
+        if self.history_length < 3 {
+            self.history_length += 1;
+        }
+        if self.history_length >= 2 {
+            self.bigrams[self.history & 0xff] += 1;
+        }
+        if self.history_length >= 3 {
+            self.trigrams[self.history] += 1;
+        }
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &NgramCounter) {
+        for (count, other) in self.bigrams.iter_mut().zip(other.bigrams.iter()) {
+            *count += other;
+        }
+        for (count, other) in self.trigrams.iter_mut().zip(other.trigrams.iter()) {
+            *count += other;
+        }
+    }
+
+    #[inline]
+    fn zero(&mut self) {
+        for count in self.bigrams.iter_mut().chain(self.trigrams.iter_mut()) {
+            *count = 0;
+        }
+    }
+
+    /// The `top` most frequent n-grams with their share of all the n-grams
+    /// of the same length counted since the last report.
+    pub fn top(&self, length: usize) -> Vec<(Vec<Instruction>, f64)> {
+        let counts = if length == 2 { &self.bigrams } else { &self.trigrams };
+        let total: usize = counts.iter().sum();
+        let mut ranked: Vec<(usize, usize)> = counts.iter()
+            .cloned()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.iter()
+            .take(self.top)
+            .map(|(key, count)| {
+                let instructions = (0..length).rev()
+                    .map(|i| Instruction::from(((key >> (4 * i)) & 0x0f) as u8))
+                    .collect();
+                (instructions, *count as f64 / total as f64)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for NgramCounter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut first = true;
+        for length in 2..4 {
+            for (instructions, share) in self.top(length) {
+                if !first {
+                    write!(f, ",")?;
+                }
+                first = false;
+                for (i, instruction) in instructions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ">")?;
+                    }
+                    write!(f, "{}", instruction)?;
+                }
+                write!(f, ":{:04}", share)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineageKey {
+    Lineage,
+    Genotype,
+}
+
+#[derive(Clone, Default)]
+pub struct LineageCounters {
+    pub cell_executions: usize,
+    pub instruction_executions: usize,
+    pub kills: usize,
+    pub killed: usize,
+    pub shares: usize,
+    pub shared: usize,
+    pub offspring: usize,
+    pub replaced: usize,
+}
+
+impl LineageCounters {
+    #[inline]
+    fn merge(&mut self, other: &LineageCounters) {
+        self.cell_executions += other.cell_executions;
+        self.instruction_executions += other.instruction_executions;
+        self.kills += other.kills;
+        self.killed += other.killed;
+        self.shares += other.shares;
+        self.shared += other.shared;
+        self.offspring += other.offspring;
+        self.replaced += other.replaced;
+    }
+
+    #[inline]
+    pub fn metabolism(&self) -> usize {
+        self.instruction_executions.checked_div(self.cell_executions).unwrap_or(0)
+    }
+}
+
+/// Statistics broken down by the lineage (or the genotype) of the cells
+/// involved. Only executions of cells with energy are attributed, and
+/// kills, shares and replacements are counted both for the lineage acting
+/// and for the lineage on the receiving end.
+pub struct LineageStatistics {
+    key: LineageKey,
+    top: usize,
+    counters: HashMap<u64, LineageCounters>,
+}
+
+impl LineageStatistics {
+    pub(crate) fn new(key: LineageKey, top: usize) -> LineageStatistics {
+        LineageStatistics {
+            key,
+            top,
+            counters: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn key<G: GenomeBuffer>(&self, cell: &Cell<G>) -> u64 {
+        match self.key {
+            LineageKey::Lineage => cell.lineage.0 as u64,
+            LineageKey::Genotype => cell.genome().fingerprint(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn counters(&mut self, key: u64) -> &mut LineageCounters {
+        self.counters.entry(key).or_default()
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &LineageStatistics) {
+        for (key, counters) in other.counters.iter() {
+            self.counters(*key).merge(counters);
+        }
+    }
+
+    #[inline]
+    fn zero(&mut self) {
+        self.counters.clear();
+    }
+
+    /// The `top` groups that executed the most instructions since the last
+    /// report.
+    pub fn top(&self) -> Vec<(u64, &LineageCounters)> {
+        let mut ranked: Vec<(u64, &LineageCounters)> = self.counters.iter()
+            .map(|(key, counters)| (*key, counters))
+            .collect();
+        ranked.sort_by(|a, b| b.1.instruction_executions.cmp(&a.1.instruction_executions)
+            .then(a.0.cmp(&b.0)));
+        ranked.truncate(self.top);
+        ranked
+    }
+}
+
+impl fmt::Display for LineageStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (key, counters) in self.top() {
+            match self.key {
+                LineageKey::Lineage => write!(f, "{}", key)?,
+                LineageKey::Genotype => write!(f, "{:016x}", key)?,
+            }
+            writeln!(f, ",{},{},{},{},{},{},{},{}",
+                     counters.cell_executions,
+                     counters.metabolism(),
+                     counters.kills,
+                     counters.killed,
+                     counters.shares,
+                     counters.shared,
+                     counters.offspring,
+                     counters.replaced)?;
+        }
+        Ok(())
+    }
+}
+
+/// How often every position of the pond was executed over a whole run,
+/// to check that cells are picked uniformly. Reports hold Pearson's
+/// chi-square statistic against the uniform distribution for the
+/// positions, the columns and the rows, its degrees of freedom and the
+/// matching standard score, which stays within a few units of 0 for
+/// uniform picks. Only uniform scheduling is expected to pass.
+pub struct PositionHistogram {
+    counts: Vec<usize>,
+    width: usize,
+    height: usize,
+}
+
+impl PositionHistogram {
+    pub(crate) fn new(width: usize, height: usize) -> PositionHistogram {
+        PositionHistogram {
+            counts: vec![0; width * height],
+            width,
+            height,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, position: &CellPosition) {
+        self.counts[position.1 * self.width + position.0] += 1;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &PositionHistogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+
+    #[inline]
+    fn zero(&mut self) {
+        for count in self.counts.iter_mut() {
+            *count = 0;
+        }
+    }
+
+    #[inline]
+    pub fn count(&self, x: usize, y: usize) -> usize {
+        self.counts[y * self.width + x]
+    }
+
+    pub fn columns(&self) -> Vec<usize> {
+        (0..self.width).map(|x| (0..self.height).map(|y| self.count(x, y)).sum()).collect()
+    }
+
+    pub fn rows(&self) -> Vec<usize> {
+        self.counts.chunks(self.width).map(|row| row.iter().sum()).collect()
+    }
+}
+
+/// Pearson's chi-square statistic of `counts` against equal expected
+/// counts, with its degrees of freedom.
+fn chi_square(counts: &[usize]) -> (f64, usize) {
+    let expected = counts.iter().sum::<usize>() as f64 / counts.len() as f64;
+    let statistic = counts.iter()
+        .map(|count| (*count as f64 - expected) * (*count as f64 - expected) / expected)
+        .sum();
+    (statistic, counts.len() - 1)
+}
+
+impl fmt::Display for PositionHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (name, counts) in [("cells", self.counts.clone()), ("columns", self.columns()), ("rows", self.rows())] {
+            let (statistic, freedom) = chi_square(&counts);
+            writeln!(f, "{},{:.1},{},{:.2}",
+                     name, statistic, freedom,
+                     (statistic - freedom as f64) / (2.0 * freedom as f64).sqrt())?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Statistics {
     pub(crate) instruction_executions: InstructionCounter,
     pub(crate) cell_executions: usize,
@@ -97,6 +395,16 @@ pub struct Statistics {
     pub(crate) viable_cells_killed: usize,
     pub(crate) viable_cell_shares: usize,
     pub(crate) viable_cell_replaced: usize,
+    pub(crate) ngrams: Option<NgramCounter>,
+    pub(crate) lineages: Option<LineageStatistics>,
+    pub(crate) reproductions: Option<ReproductionLog>,
+    pub(crate) positions: Option<PositionHistogram>,
+}
+
+impl Default for Statistics {
+    fn default() -> Statistics {
+        Statistics::new()
+    }
 }
 
 impl Statistics {
@@ -108,6 +416,10 @@ impl Statistics {
             viable_cells_killed: 0,
             viable_cell_shares: 0,
             viable_cell_replaced: 0,
+            ngrams: None,
+            lineages: None,
+            reproductions: None,
+            positions: None,
         }
     }
 
@@ -120,15 +432,108 @@ impl Statistics {
         for i in Instruction::iterator() {
             self.instruction_executions[i] = 0;
         }
+        if let Some(ref mut ngrams) = self.ngrams {
+            ngrams.zero();
+        }
+        if let Some(ref mut lineages) = self.lineages {
+            lineages.zero();
+        }
+    }
+
+    /// Empty statistics tracking the same breakdowns, for a worker whose
+    /// counts get merged back with `merge`.
+    pub(crate) fn worker(&self) -> Statistics {
+        let mut statistics = Statistics::new();
+        statistics.clock = self.clock;
+        statistics.ngrams = self.ngrams.as_ref().map(|ngrams| NgramCounter::new(ngrams.top));
+        statistics.lineages = self.lineages.as_ref()
+            .map(|lineages| LineageStatistics::new(lineages.key, lineages.top));
+        statistics.positions = self.positions.as_ref()
+            .map(|positions| PositionHistogram::new(positions.width, positions.height));
+        statistics
+    }
+
+    /// Adds the counts of `other` and zeroes them.
+    pub(crate) fn merge(&mut self, other: &mut Statistics) {
+        self.cell_executions += other.cell_executions;
+        self.viable_cells_killed += other.viable_cells_killed;
+        self.viable_cell_shares += other.viable_cell_shares;
+        self.viable_cell_replaced += other.viable_cell_replaced;
+        for i in Instruction::iterator() {
+            self.instruction_executions[i] += other.instruction_executions[i];
+        }
+        if let (Some(ngrams), Some(other)) = (self.ngrams.as_mut(), other.ngrams.as_ref()) {
+            ngrams.merge(other);
+        }
+        if let (Some(lineages), Some(other)) = (self.lineages.as_mut(), other.lineages.as_ref()) {
+            lineages.merge(other);
+        }
+        if let (Some(positions), Some(other)) = (self.positions.as_mut(), other.positions.as_mut()) {
+            positions.merge(other);
+            other.zero();
+        }
+        other.zero();
+    }
+
+    /// Counts bigrams and trigrams of executed instructions, keeping the
+    /// `top` most frequent of each in reports.
+    #[inline]
+    pub fn track_ngrams(&mut self, top: usize) {
+        self.ngrams = Some(NgramCounter::new(top));
+    }
+
+    #[inline]
+    pub fn ngrams(&self) -> Option<&NgramCounter> {
+        self.ngrams.as_ref()
+    }
+
+    /// Attributes executions and interactions to the lineage or genotype of
+    /// the cells involved, keeping the `top` most active groups in reports.
+    #[inline]
+    pub fn track_lineages(&mut self, key: LineageKey, top: usize) {
+        self.lineages = Some(LineageStatistics::new(key, top));
+    }
+
+    #[inline]
+    pub fn lineages(&self) -> Option<&LineageStatistics> {
+        self.lineages.as_ref()
+    }
+
+    /// Counts the executions at every position of a `width` by `height`
+    /// pond for the whole run, see `PositionHistogram`.
+    #[inline]
+    pub fn track_positions(&mut self, width: usize, height: usize) {
+        self.positions = Some(PositionHistogram::new(width, height));
+    }
+
+    #[inline]
+    pub fn positions(&self) -> Option<&PositionHistogram> {
+        self.positions.as_ref()
+    }
+
+    #[inline]
+    pub fn log_reproductions(&mut self, log: ReproductionLog) {
+        self.reproductions = Some(log);
+    }
+
+    #[inline]
+    pub fn reproduction_log(&mut self) -> Option<&mut ReproductionLog> {
+        self.reproductions.as_mut()
+    }
+
+    #[inline]
+    pub fn cell_executions(&self) -> usize {
+        self.cell_executions
+    }
+
+    #[inline]
+    pub fn instruction_executions(&self) -> usize {
+        self.total_metabolism()
     }
 
     #[inline]
     pub fn metabolism(&self) -> usize {
-        if self.cell_executions == 0 {
-            0
-        } else {
-            self.total_metabolism() / self.cell_executions
-        }
+        self.total_metabolism().checked_div(self.cell_executions).unwrap_or(0)
     }
 
     #[inline]
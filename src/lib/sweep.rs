@@ -0,0 +1,166 @@
+use std::fmt;
+use batch::Batch;
+use config::Config;
+
+/// One combination of parameter values.
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub name: String,
+    pub settings: Vec<(String, String)>,
+}
+
+impl Condition {
+    pub fn config(&self) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (key, value) in &self.settings {
+            config.set(key, value).map_err(|error| format!("{}: {}", self.name, error))?;
+        }
+        Ok(config)
+    }
+}
+
+/// A set of conditions to run as batches of replicates, read from an
+/// experiment file such as
+///
+/// ```text
+/// replicates = 10
+/// ticks = 1000000
+/// seed = 42
+/// mutation_rate = 1000, 5000, 20000
+/// pond_size = 400x300, 800x600
+/// ```
+///
+/// Lines listing several values are crossed into a grid. Instead of a grid,
+/// conditions can be listed one per line as `condition = key=value ...`.
+/// Lines starting with `#` are ignored.
+pub struct Sweep {
+    pub replicates: usize,
+    pub ticks: usize,
+    pub seed: usize,
+    pub conditions: Vec<Condition>,
+}
+
+impl Sweep {
+    pub fn parse(text: &str) -> Result<Sweep, String> {
+        let mut replicates = 10;
+        let mut ticks = None;
+        let mut seed = 0;
+        let mut axes: Vec<(String, Vec<String>)> = Vec::new();
+        let mut listed = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid line {}: {}", number + 1, line);
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(invalid()),
+            };
+            let number = || value.parse::<usize>().map_err(|_| invalid());
+            match key {
+                "replicates" => replicates = number()?,
+                "ticks" => ticks = Some(number()?),
+                "seed" => seed = number()?,
+                "condition" => {
+                    let settings = value.split_whitespace()
+                        .map(|setting| {
+                            let mut parts = setting.splitn(2, '=');
+                            match (parts.next(), parts.next()) {
+                                (Some(key), Some(value)) => Ok((key.to_string(), value.to_string())),
+                                _ => Err(invalid()),
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    listed.push(settings);
+                },
+                _ => axes.push((key.to_string(),
+                                value.split(',').map(|value| value.trim().to_string()).collect())),
+            }
+        }
+        let settings = if listed.is_empty() {
+            grid(&axes)
+        } else if axes.is_empty() {
+            listed
+        } else {
+            return Err("Conditions can't be listed together with a grid".to_string());
+        };
+        let conditions = settings.into_iter()
+            .enumerate()
+            .map(|(index, settings)| Condition { name: format!("condition-{}", index), settings })
+            .collect::<Vec<_>>();
+        for condition in &conditions {
+            condition.config()?;
+        }
+        Ok(Sweep {
+            replicates,
+            ticks: ticks.ok_or("Missing ticks")?,
+            seed,
+            conditions,
+        })
+    }
+
+    /// The batch for `condition`, every condition sharing the same seeds.
+    pub fn batch(&self, condition: &Condition, threads: usize) -> Result<Batch, String> {
+        Ok(Batch {
+            config: condition.config()?,
+            replicates: self.replicates,
+            ticks: self.ticks,
+            threads,
+            seed: self.seed,
+        })
+    }
+
+    /// The conditions and their parameter values as CSV with a header.
+    pub fn index(&self) -> Index<'_> {
+        Index { sweep: self }
+    }
+}
+
+fn grid(axes: &[(String, Vec<String>)]) -> Vec<Vec<(String, String)>> {
+    axes.iter().fold(vec![Vec::new()], |conditions, (key, values)| {
+        conditions.iter()
+            .flat_map(|settings| values.iter().map(move |value| {
+                let mut settings = settings.clone();
+                settings.push((key.clone(), value.clone()));
+                settings
+            }))
+            .collect()
+    })
+}
+
+pub struct Index<'a> {
+    sweep: &'a Sweep,
+}
+
+impl<'a> fmt::Display for Index<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut keys: Vec<&str> = Vec::new();
+        for condition in &self.sweep.conditions {
+            for (key, _) in &condition.settings {
+                if !keys.contains(&key.as_str()) {
+                    keys.push(key);
+                }
+            }
+        }
+        write!(f, "condition")?;
+        for key in &keys {
+            write!(f, ",{}", key)?;
+        }
+        writeln!(f)?;
+        for condition in &self.sweep.conditions {
+            write!(f, "{}", condition.name)?;
+            for key in &keys {
+                let value = condition.settings.iter()
+                    .rev()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.as_str())
+                    .unwrap_or("");
+                write!(f, ",{}", value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+use std::str::FromStr;
+use cell::CellPosition;
+
+/// Where a cell looks. `Up` is towards larger `y`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Facing {
+    Up,
+    Down,
+    Right,
+    Left,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+/// The von Neumann decoding, the one the pond always used.
+impl From<u8> for Facing {
+    fn from(byte: u8) -> Facing {
+        match byte & 0x3 {
+            0x0 => Facing::Left,
+            0x1 => Facing::Right,
+            0x2 => Facing::Up,
+            0x3 => Facing::Down,
+            _ => panic!("Can't happen"),
+        }
+    }
+}
+
+/// Which cells are the neighbors of a cell, and how `TURN` picks one of
+/// them from the register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    /// The 4 orthogonal neighbors.
+    VonNeumann,
+    /// The 8 orthogonal and diagonal neighbors.
+    Moore,
+    /// 6 neighbors, with the odd rows shifted half a cell to the right.
+    /// Wrapping around keeps the layout only with an even height.
+    Hex,
+}
+
+/// What lies beyond the edges of the pond.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// The opposite edge.
+    Torus,
+    /// Nothing, interactions across the edge fail.
+    Walls,
+    /// The edge mirrors: a cell facing out of the pond faces back in.
+    Reflective,
+}
+
+const MOORE: [Facing; 8] = [
+    Facing::Left, Facing::Right, Facing::Up, Facing::Down,
+    Facing::UpLeft, Facing::UpRight, Facing::DownLeft, Facing::DownRight,
+];
+
+const HEX: [Facing; 6] = [
+    Facing::Left, Facing::Right,
+    Facing::UpLeft, Facing::UpRight, Facing::DownLeft, Facing::DownRight,
+];
+
+impl Topology {
+    /// The directions `facing` can decode to.
+    #[inline]
+    pub fn directions(&self) -> &'static [Facing] {
+        match self {
+            Topology::VonNeumann => &MOORE[..4],
+            Topology::Moore => &MOORE,
+            Topology::Hex => &HEX,
+        }
+    }
+
+    /// The direction `register` turns to, if any. Every direction takes
+    /// the same number of register values, so on a hex grid 12 to 15 leave
+    /// the facing as it is.
+    #[inline]
+    pub fn facing(&self, register: u8) -> Option<Facing> {
+        match self {
+            Topology::VonNeumann => Some(Facing::from(register)),
+            Topology::Moore => Some(MOORE[(register & 0x7) as usize]),
+            Topology::Hex if register & 0xf < 12 => Some(HEX[(register % 6) as usize]),
+            Topology::Hex => None,
+        }
+    }
+
+    /// The neighbor of `position` towards `facing` in a `width` by `height`
+    /// pond, if there is one. On a hex grid `Up` and `Down` are the
+    /// neighbor above or below in the same column.
+    #[inline]
+    pub fn neighbor_position(&self,
+                             position: &CellPosition,
+                             facing: &Facing,
+                             width: usize,
+                             height: usize,
+                             boundary: Boundary) -> Option<CellPosition> {
+        let (dx, dy) = match facing {
+            Facing::Left => (-1, 0),
+            Facing::Right => (1, 0),
+            Facing::Up => (0, 1),
+            Facing::Down => (0, -1),
+            Facing::UpLeft => (-1, 1),
+            Facing::UpRight => (1, 1),
+            Facing::DownLeft => (-1, -1),
+            Facing::DownRight => (1, -1),
+        };
+        let dx = match self {
+            // Diagonal neighbors of an even row are up and down the same
+            // column and the one to its left, those of an odd row the same
+            // column and the one to its right.
+            Topology::Hex if dy != 0 && position.1.is_multiple_of(2) => dx.min(0),
+            Topology::Hex if dy != 0 => dx.max(0),
+            _ => dx,
+        };
+        Some(CellPosition(step(position.0, dx, width, boundary)?, step(position.1, dy, height, boundary)?))
+    }
+}
+
+#[inline]
+fn step(coordinate: usize, delta: isize, length: usize, boundary: Boundary) -> Option<usize> {
+    let outside = (delta == -1 && coordinate == 0) || (delta == 1 && coordinate == length - 1);
+    match boundary {
+        Boundary::Walls if outside => None,
+        Boundary::Reflective if outside => Some((coordinate as isize - delta) as usize),
+        _ => Some(((coordinate + length) as isize + delta) as usize % length),
+    }
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Boundary::Torus => write!(f, "torus"),
+            Boundary::Walls => write!(f, "walls"),
+            Boundary::Reflective => write!(f, "reflective"),
+        }
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Boundary, String> {
+        match name {
+            "torus" => Ok(Boundary::Torus),
+            "walls" => Ok(Boundary::Walls),
+            "reflective" => Ok(Boundary::Reflective),
+            _ => Err(format!("Unknown boundary {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Topology::VonNeumann => write!(f, "von-neumann"),
+            Topology::Moore => write!(f, "moore"),
+            Topology::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Topology, String> {
+        match name {
+            "von-neumann" => Ok(Topology::VonNeumann),
+            "moore" => Ok(Topology::Moore),
+            "hex" => Ok(Topology::Hex),
+            _ => Err(format!("Unknown topology {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 4;
+
+    fn bounded_neighbors(topology: Topology, boundary: Boundary, x: usize, y: usize)
+                         -> Vec<(Facing, Option<(usize, usize)>)> {
+        topology.directions().iter()
+            .map(|facing| {
+                let neighbor = topology.neighbor_position(&CellPosition(x, y), facing, WIDTH, HEIGHT, boundary);
+                (*facing, neighbor.map(|neighbor| (neighbor.0, neighbor.1)))
+            })
+            .collect()
+    }
+
+    fn neighbors(topology: Topology, x: usize, y: usize) -> Vec<(Facing, (usize, usize))> {
+        bounded_neighbors(topology, Boundary::Torus, x, y).into_iter()
+            .map(|(facing, neighbor)| (facing, neighbor.expect("A torus has no edge")))
+            .collect()
+    }
+
+    #[test]
+    fn von_neumann_neighbors() {
+        assert_eq!(neighbors(Topology::VonNeumann, 2, 1), vec![
+            (Facing::Left, (1, 1)),
+            (Facing::Right, (3, 1)),
+            (Facing::Up, (2, 2)),
+            (Facing::Down, (2, 0)),
+        ]);
+    }
+
+    #[test]
+    fn von_neumann_neighbors_wrap_around() {
+        assert_eq!(neighbors(Topology::VonNeumann, 0, 0), vec![
+            (Facing::Left, (4, 0)),
+            (Facing::Right, (1, 0)),
+            (Facing::Up, (0, 1)),
+            (Facing::Down, (0, 3)),
+        ]);
+        assert_eq!(neighbors(Topology::VonNeumann, 4, 3), vec![
+            (Facing::Left, (3, 3)),
+            (Facing::Right, (0, 3)),
+            (Facing::Up, (4, 0)),
+            (Facing::Down, (4, 2)),
+        ]);
+    }
+
+    #[test]
+    fn moore_neighbors() {
+        assert_eq!(neighbors(Topology::Moore, 2, 1), vec![
+            (Facing::Left, (1, 1)),
+            (Facing::Right, (3, 1)),
+            (Facing::Up, (2, 2)),
+            (Facing::Down, (2, 0)),
+            (Facing::UpLeft, (1, 2)),
+            (Facing::UpRight, (3, 2)),
+            (Facing::DownLeft, (1, 0)),
+            (Facing::DownRight, (3, 0)),
+        ]);
+    }
+
+    #[test]
+    fn moore_neighbors_wrap_around() {
+        assert_eq!(neighbors(Topology::Moore, 0, 0), vec![
+            (Facing::Left, (4, 0)),
+            (Facing::Right, (1, 0)),
+            (Facing::Up, (0, 1)),
+            (Facing::Down, (0, 3)),
+            (Facing::UpLeft, (4, 1)),
+            (Facing::UpRight, (1, 1)),
+            (Facing::DownLeft, (4, 3)),
+            (Facing::DownRight, (1, 3)),
+        ]);
+        assert_eq!(neighbors(Topology::Moore, 4, 3), vec![
+            (Facing::Left, (3, 3)),
+            (Facing::Right, (0, 3)),
+            (Facing::Up, (4, 0)),
+            (Facing::Down, (4, 2)),
+            (Facing::UpLeft, (3, 0)),
+            (Facing::UpRight, (0, 0)),
+            (Facing::DownLeft, (3, 2)),
+            (Facing::DownRight, (0, 2)),
+        ]);
+    }
+
+    #[test]
+    fn hex_neighbors() {
+        assert_eq!(neighbors(Topology::Hex, 2, 2), vec![
+            (Facing::Left, (1, 2)),
+            (Facing::Right, (3, 2)),
+            (Facing::UpLeft, (1, 3)),
+            (Facing::UpRight, (2, 3)),
+            (Facing::DownLeft, (1, 1)),
+            (Facing::DownRight, (2, 1)),
+        ]);
+        assert_eq!(neighbors(Topology::Hex, 2, 1), vec![
+            (Facing::Left, (1, 1)),
+            (Facing::Right, (3, 1)),
+            (Facing::UpLeft, (2, 2)),
+            (Facing::UpRight, (3, 2)),
+            (Facing::DownLeft, (2, 0)),
+            (Facing::DownRight, (3, 0)),
+        ]);
+    }
+
+    #[test]
+    fn hex_neighbors_wrap_around() {
+        assert_eq!(neighbors(Topology::Hex, 0, 0), vec![
+            (Facing::Left, (4, 0)),
+            (Facing::Right, (1, 0)),
+            (Facing::UpLeft, (4, 1)),
+            (Facing::UpRight, (0, 1)),
+            (Facing::DownLeft, (4, 3)),
+            (Facing::DownRight, (0, 3)),
+        ]);
+        assert_eq!(neighbors(Topology::Hex, 4, 3), vec![
+            (Facing::Left, (3, 3)),
+            (Facing::Right, (0, 3)),
+            (Facing::UpLeft, (4, 0)),
+            (Facing::UpRight, (0, 0)),
+            (Facing::DownLeft, (4, 2)),
+            (Facing::DownRight, (0, 2)),
+        ]);
+    }
+
+    #[test]
+    fn hex_neighbors_are_mutual() {
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                for (_, (nx, ny)) in neighbors(Topology::Hex, x, y) {
+                    assert!(neighbors(Topology::Hex, nx, ny).iter().any(|(_, n)| *n == (x, y)),
+                            "({}, {}) isn't a neighbor of its neighbor ({}, {})", x, y, nx, ny);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn walls_have_no_neighbors_beyond_the_edge() {
+        assert_eq!(bounded_neighbors(Topology::Moore, Boundary::Walls, 0, 0), vec![
+            (Facing::Left, None),
+            (Facing::Right, Some((1, 0))),
+            (Facing::Up, Some((0, 1))),
+            (Facing::Down, None),
+            (Facing::UpLeft, None),
+            (Facing::UpRight, Some((1, 1))),
+            (Facing::DownLeft, None),
+            (Facing::DownRight, None),
+        ]);
+        assert_eq!(bounded_neighbors(Topology::Hex, Boundary::Walls, 4, 3), vec![
+            (Facing::Left, Some((3, 3))),
+            (Facing::Right, None),
+            (Facing::UpLeft, None),
+            (Facing::UpRight, None),
+            (Facing::DownLeft, Some((4, 2))),
+            (Facing::DownRight, None),
+        ]);
+        assert_eq!(bounded_neighbors(Topology::Moore, Boundary::Walls, 2, 1),
+                   bounded_neighbors(Topology::Moore, Boundary::Torus, 2, 1));
+    }
+
+    #[test]
+    fn reflective_edges_face_back_in() {
+        assert_eq!(neighbors_reflected(Topology::Moore, 0, 0), vec![
+            (Facing::Left, (1, 0)),
+            (Facing::Right, (1, 0)),
+            (Facing::Up, (0, 1)),
+            (Facing::Down, (0, 1)),
+            (Facing::UpLeft, (1, 1)),
+            (Facing::UpRight, (1, 1)),
+            (Facing::DownLeft, (1, 1)),
+            (Facing::DownRight, (1, 1)),
+        ]);
+        assert_eq!(neighbors_reflected(Topology::VonNeumann, 4, 3), vec![
+            (Facing::Left, (3, 3)),
+            (Facing::Right, (3, 3)),
+            (Facing::Up, (4, 2)),
+            (Facing::Down, (4, 2)),
+        ]);
+    }
+
+    fn neighbors_reflected(topology: Topology, x: usize, y: usize) -> Vec<(Facing, (usize, usize))> {
+        bounded_neighbors(topology, Boundary::Reflective, x, y).into_iter()
+            .map(|(facing, neighbor)| (facing, neighbor.expect("Reflective edges have a neighbor")))
+            .collect()
+    }
+
+    #[test]
+    fn registers_decode_to_every_direction_alike() {
+        for topology in [Topology::VonNeumann, Topology::Moore, Topology::Hex].iter() {
+            let decoded: Vec<Facing> = (0..16).filter_map(|register| topology.facing(register)).collect();
+            for facing in topology.directions() {
+                let count = decoded.iter().filter(|decoded| *decoded == facing).count();
+                assert_eq!(count, decoded.len() / topology.directions().len(),
+                           "{} faces {:?} from {} registers", topology, facing, count);
+            }
+            for facing in decoded.iter() {
+                assert!(topology.directions().contains(facing));
+            }
+        }
+    }
+}
@@ -1,8 +1,375 @@
 extern crate rustedpond;
 
 use rustedpond::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+use std::slice::Iter;
+use std::str::FromStr;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+type Command = fn(&[String]) -> Result<(), String>;
+
+struct Options {
+    config: Config,
+    classify: bool,
+    share_genomes: bool,
+    ngrams: Option<usize>,
+    lineages: Option<String>,
+    lineage_key: LineageKey,
+    lineage_top: usize,
+    motifs: Option<String>,
+    position_check: Option<String>,
+    obstacles: Option<String>,
+    energy: Option<String>,
+    inflow_weights: Option<String>,
+    genome_mask: Option<String>,
+    genomes: Vec<String>,
+    genome_bank: Option<String>,
+    bank_share: Option<f64>,
+    bank_mutation: Option<f64>,
+    disturbances: Option<String>,
+    disturbance_log: Option<String>,
+    reproduction_log: Option<String>,
+    checkpoint: Option<String>,
+    events: Option<String>,
+    reproduction_log_format: LogFormat,
+    motif_options: MotifOptions,
+    seed: Option<usize>,
+    tiles: Option<(usize, usize)>,
+    threads: usize,
+    round: usize,
+    ticks: usize,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            config: Config::default(),
+            classify: false,
+            share_genomes: false,
+            ngrams: None,
+            lineages: None,
+            lineage_key: LineageKey::Lineage,
+            lineage_top: 10,
+            motifs: None,
+            position_check: None,
+            obstacles: None,
+            energy: None,
+            inflow_weights: None,
+            genome_mask: None,
+            genomes: Vec::new(),
+            genome_bank: None,
+            bank_share: None,
+            bank_mutation: None,
+            disturbances: None,
+            disturbance_log: None,
+            reproduction_log: None,
+            checkpoint: None,
+            events: None,
+            reproduction_log_format: LogFormat::Ndjson,
+            motif_options: MotifOptions::default(),
+            seed: None,
+            tiles: None,
+            threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            round: 20000,
+            ticks: 0,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--classify" => options.classify = true,
+                "--share-genomes" => options.share_genomes = true,
+                "--ngrams" => options.ngrams = Some(parse_value(&mut args, arg)?),
+                "--lineages" => options.lineages = Some(value(&mut args, arg)?),
+                "--lineage-key" => options.lineage_key = match value(&mut args, arg)?.as_str() {
+                    "lineage" => LineageKey::Lineage,
+                    "genotype" => LineageKey::Genotype,
+                    key => return Err(format!("Invalid value {} for {}", key, arg)),
+                },
+                "--lineage-top" => options.lineage_top = parse_value(&mut args, arg)?,
+                "--motifs" => options.motifs = Some(value(&mut args, arg)?),
+                "--motif-length" => options.motif_options.length = parse_value(&mut args, arg)?,
+                "--motif-top" => options.motif_options.top = parse_value(&mut args, arg)?,
+                "--motif-min-generation" =>
+                    options.motif_options.min_generation = parse_value(&mut args, arg)?,
+                "--position-check" => options.position_check = Some(value(&mut args, arg)?),
+                "--obstacles" => options.obstacles = Some(value(&mut args, arg)?),
+                "--energy" => options.energy = Some(value(&mut args, arg)?),
+                "--inflow-weights" => options.inflow_weights = Some(value(&mut args, arg)?),
+                "--genome-mask" => options.genome_mask = Some(value(&mut args, arg)?),
+                "--genome" => options.genomes.push(value(&mut args, arg)?),
+                "--genome-bank" => options.genome_bank = Some(value(&mut args, arg)?),
+                "--bank-share" => options.bank_share = Some(parse_value(&mut args, arg)?),
+                "--bank-mutation" => options.bank_mutation = Some(parse_value(&mut args, arg)?),
+                "--disturbances" => options.disturbances = Some(value(&mut args, arg)?),
+                "--disturbance-log" => options.disturbance_log = Some(value(&mut args, arg)?),
+                "--checkpoint" => options.checkpoint = Some(value(&mut args, arg)?),
+                "--events" => options.events = Some(value(&mut args, arg)?),
+                "--reproduction-log" => options.reproduction_log = Some(value(&mut args, arg)?),
+                "--reproduction-log-format" =>
+                    options.reproduction_log_format = match value(&mut args, arg)?.as_str() {
+                        "ndjson" => LogFormat::Ndjson,
+                        "binary" => LogFormat::Binary,
+                        format => return Err(format!("Invalid value {} for {}", format, arg)),
+                    },
+                "--seed" => options.seed = Some(parse_value(&mut args, arg)?),
+                "--tiles" => {
+                    let tiles = value(&mut args, arg)?;
+                    let mut dimensions = tiles.split('x').map(|n| n.parse::<usize>());
+                    options.tiles = match (dimensions.next(), dimensions.next(), dimensions.next()) {
+                        (Some(Ok(columns)), Some(Ok(rows)), None) => Some((columns, rows)),
+                        _ => return Err(format!("Invalid value {} for {}", tiles, arg)),
+                    };
+                },
+                "--threads" => options.threads = parse_value(&mut args, arg)?,
+                "--round" => options.round = parse_value(&mut args, arg)?,
+                "--ticks" => options.ticks = parse_value(&mut args, arg)?,
+                "--set" => set_parameter(&mut options.config, &value(&mut args, arg)?)?,
+                "--schedule" => {
+                    let path = value(&mut args, arg)?;
+                    fs::read_to_string(&path)
+                        .map_err(|error| format!("Can't read {}: {}", path, error))
+                        .and_then(|text| options.config.schedules.parse_changes(&text)
+                            .map_err(|error| format!("Can't parse {}: {}", path, error)))?;
+                },
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+        if options.tiles.is_some() && (options.events.is_some() || options.reproduction_log.is_some()) {
+            return Err("Events and reproductions can't be logged with --tiles".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn value(args: &mut Iter<String>, name: &str) -> Result<String, String> {
+    args.next().cloned().ok_or(format!("Missing value for {}", name))
+}
+
+fn parse_value<T: FromStr>(args: &mut Iter<String>, name: &str) -> Result<T, String> {
+    let value = value(args, name)?;
+    value.parse().map_err(|_| format!("Invalid value {} for {}", value, name))
+}
+
+fn set_parameter(config: &mut Config, setting: &str) -> Result<(), String> {
+    let mut parts = setting.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => config.set(key.trim(), value),
+        _ => Err(format!("Invalid setting {}, expected <key>=<value>", setting)),
+    }
+}
+
+struct Reporter {
+    options: Options,
+    lineages: Option<BufWriter<File>>,
+    motifs: Option<BufWriter<File>>,
+    positions: Option<BufWriter<File>>,
+}
+
+impl Reporter {
+    fn new(options: Options) -> Result<Reporter, String> {
+        let lineages = create_report(&options.lineages)?;
+        let motifs = create_report(&options.motifs)?;
+        let positions = create_report(&options.position_check)?;
+        Ok(Reporter { options, lineages, motifs, positions })
+    }
+}
+
+fn create_report(path: &Option<String>) -> Result<Option<BufWriter<File>>, String> {
+    match path {
+        Some(path) => File::create(path)
+            .map(|file| Some(BufWriter::new(file)))
+            .map_err(|error| format!("Can't create {}: {}", path, error)),
+        None => Ok(None),
+    }
+}
+
+fn write_report<T: ToString>(file: &mut BufWriter<File>, clock: usize, report: &T) {
+    for line in report.to_string().lines() {
+        writeln!(file, "{},{}", clock, line).expect("Can't write report");
+    }
+    file.flush().expect("Can't write report");
+}
+
+fn read_genome(path: &str) -> Result<Genome, String> {
+    fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path, error))?
+        .parse()
+        .map_err(|error| format!("Can't parse {}: {}", path, error))
+}
+
+fn read_mask(path: &str) -> Result<Mask, String> {
+    let bytes = fs::read(path).map_err(|error| format!("Can't read {}: {}", path, error))?;
+    Mask::read(&bytes).map_err(|error| format!("Can't parse {}: {}", path, error))
+}
+
+/// Sets up the starting pond from the masks given on the command line,
+/// obstacles first so that nothing else is placed on them.
+fn apply_masks<G: GenomeBuffer>(simulation: &mut Simulation<G>, options: &Options) -> Result<(), String> {
+    if options.genome_mask.is_none() && !options.genomes.is_empty() {
+        return Err("--genome needs --genome-mask".to_string());
+    }
+    let in_mask = |path: &str, result: Result<(), String>| result.map_err(|error| format!("{}: {}", path, error));
+    if let Some(ref path) = options.obstacles {
+        in_mask(path, simulation.place_obstacles(&read_mask(path)?))?;
+    }
+    if let Some(ref path) = options.genome_mask {
+        let genomes = options.genomes.iter()
+            .map(|path| read_genome(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        in_mask(path, simulation.place_genomes(&read_mask(path)?, &genomes))?;
+    }
+    if let Some(ref path) = options.energy {
+        in_mask(path, simulation.set_energy(&read_mask(path)?))?;
+    }
+    if let Some(ref path) = options.inflow_weights {
+        let mask = read_mask(path)?;
+        in_mask(path, mask.fits(simulation.pond.width(), simulation.pond.height()))?;
+        let weights = WeightMap::from_mask(&mask).map_err(|error| format!("{}: {}", path, error))?;
+        simulation.set_inflow_strategy(Box::new(weights));
+    }
+    Ok(())
+}
+
+/// The genome bank for the inflow, if one is given, mixed and mutated as
+/// the options say.
+fn read_genome_bank(options: &Options) -> Result<Option<GenomeBank>, String> {
+    let path = match options.genome_bank {
+        Some(ref path) => path,
+        None if options.bank_share.is_some() || options.bank_mutation.is_some() =>
+            return Err("--bank-share and --bank-mutation need --genome-bank".to_string()),
+        None => return Ok(None),
+    };
+    let in_bank = |error: String| format!("{}: {}", path, error);
+    let mut bank = fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path, error))
+        .and_then(|text| GenomeBank::parse(&text).map_err(|error| format!("Can't parse {}: {}", path, error)))?;
+    if let Some(share) = options.bank_share {
+        bank.set_share(share).map_err(in_bank)?;
+    }
+    if let Some(mutation) = options.bank_mutation {
+        bank.set_mutation(mutation).map_err(in_bank)?;
+    }
+    Ok(Some(bank))
+}
+
+/// Disturbances logged to `log`, or to the standard error.
+fn read_disturbances(path: &str, log: &Option<String>) -> Result<Disturbances, String> {
+    let mut disturbances = fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path, error))
+        .and_then(|text| Disturbances::parse(&text).map_err(|error| format!("Can't parse {}: {}", path, error)))?;
+    match log {
+        Some(log) => disturbances.log_to(Box::new(BufWriter::new(
+            File::create(log).map_err(|error| format!("Can't create {}: {}", log, error))?))),
+        None => disturbances.log_to(Box::new(io::stderr())),
+    }
+    Ok(disturbances)
+}
+
+fn diff(args: &[String]) -> Result<(), String> {
+    let mut show_matches = false;
+    let mut paths = Vec::with_capacity(2);
+    for arg in args {
+        match arg.as_str() {
+            "--all" => show_matches = true,
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    if paths.len() != 2 {
+        return Err("Usage: rustedpond diff [--all] <genome> <genome>".to_string());
+    }
+    let from = read_genome(paths[0])?;
+    let to = read_genome(paths[1])?;
+    let alignment = Alignment::new(&from, &to);
+    print!("{}", alignment.side_by_side(show_matches));
+    println!("{}", alignment);
+    Ok(())
+}
+
+fn write_checkpoint<G: GenomeBuffer>(path: &str, clock: usize, events: usize, id_generator: &CellIdGenerator, pond: &CellPond<G>)
+    -> Result<(), String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .and_then(|mut file| {
+            Checkpoint::write(&mut file, clock, events, id_generator, pond)?;
+            file.flush()
+        })
+        .map_err(|error| format!("Can't write {}: {}", path, error))
+}
+
+fn read_checkpoint(path: &str) -> Result<Checkpoint, String> {
+    File::open(path)
+        .and_then(|file| Checkpoint::read(&mut BufReader::new(file)))
+        .map_err(|error| format!("Can't read {}: {}", path, error))
+}
+
+fn replay(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut paths = Vec::with_capacity(2);
+    let mut ticks = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = Some(value(&mut args, arg)?),
+            _ if paths.len() < 2 => paths.push(arg.as_str()),
+            _ => ticks.push(arg.parse::<usize>()
+                .map_err(|_| format!("Invalid tick {}", arg))?),
+        }
+    }
+    if paths.len() != 2 {
+        return Err(
+            "Usage: rustedpond replay [--output <checkpoint>] <checkpoint> <events> <tick>...".to_string());
+    }
+    let checkpoint = read_checkpoint(paths[0])?;
+    let events = File::open(paths[1])
+        .and_then(|file| EventLog::read(&mut BufReader::new(file)))
+        .map_err(|error| format!("Can't read {}: {}", paths[1], error))?;
+    let mut replay = Replay::new(checkpoint, events);
+    if ticks.is_empty() {
+        ticks.push(replay.last_tick());
+    }
+    for tick in ticks {
+        replay.seek(tick);
+        let pond = replay.pond();
+        println!("{},{},{},{},{}",
+                 replay.clock(),
+                 pond.total_energy(),
+                 pond.total_active_cells(),
+                 pond.total_viable_replicators(),
+                 pond.max_generation());
+    }
+    if let Some(path) = output {
+        let checkpoint = replay.checkpoint();
+        write_checkpoint(&path, checkpoint.clock, checkpoint.events, &checkpoint.id_generator, &checkpoint.pond)?;
+    }
+    Ok(())
+}
+
+fn motifs(args: &[String]) -> Result<(), String> {
+    let mut options = MotifOptions::default();
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--motif-length" => options.length = parse_value(&mut args, arg)?,
+            "--motif-top" => options.top = parse_value(&mut args, arg)?,
+            "--motif-min-generation" => options.min_generation = parse_value(&mut args, arg)?,
+            _ if path.is_none() => path = Some(arg.as_str()),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    let path = path.ok_or(
+        "Usage: rustedpond motifs [--motif-length <n>] [--motif-top <n>] [--motif-min-generation <n>] <checkpoint>")?;
+    let checkpoint = read_checkpoint(path)?;
+    print!("{}", MotifReport::new(&checkpoint.pond, &options));
+    Ok(())
+}
+
 #[inline]
 fn get_timestamp() -> usize {
     let start = SystemTime::now();
@@ -11,61 +378,280 @@ fn get_timestamp() -> usize {
         .subsec_nanos() as usize
 }
 
-#[inline]
-fn mutate_cell(pond: &mut CellPond,
-               random_generator: &mut RandomGenerator,
-               id_generator: &mut CellIdGenerator) {
-    let position = &random_generator.generate_cell_position();
-    pond.replace(
-        position, id_generator.next(), Genome::random(random_generator));
+fn batch(args: &[String]) -> Result<(), String> {
+    let mut batch = Batch {
+        config: Config::default(),
+        replicates: 20,
+        ticks: 0,
+        threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+        seed: get_timestamp(),
+    };
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replicates" => batch.replicates = parse_value(&mut args, arg)?,
+            "--ticks" => batch.ticks = parse_value(&mut args, arg)?,
+            "--threads" => batch.threads = parse_value(&mut args, arg)?,
+            "--seed" => batch.seed = parse_value(&mut args, arg)?,
+            "--set" => set_parameter(&mut batch.config, &value(&mut args, arg)?)?,
+            "--output" => output = Some(PathBuf::from(value(&mut args, arg)?)),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    let output = match output {
+        Some(ref output) if batch.ticks > 0 => output,
+        _ => return Err(
+            "Usage: rustedpond batch --ticks <ticks> --output <directory> \
+             [--replicates <n>] [--threads <n>] [--seed <seed>] [--set <key>=<value>]...".to_string()),
+    };
+    run_batch(&batch, output)
 }
 
-#[inline]
-fn execute_cell(pond: &mut CellPond,
-                random_generator: &mut RandomGenerator,
-                id_generator: &mut CellIdGenerator,
-                statistics: &mut Statistics) {
-    let position = random_generator.generate_cell_position();
-    let mut vm = CellVM::new(position, pond, id_generator, random_generator, statistics);
-    vm.execute();
+fn run_batch(batch: &Batch, output: &Path) -> Result<(), String> {
+    fs::create_dir_all(output)
+        .map_err(|error| format!("Can't create {}: {}", output.display(), error))?;
+    let replicates = batch
+        .run(|replicate| {
+            File::create(output.join(format!("replicate-{}.csv", replicate))).map(BufWriter::new)
+        })
+        .map_err(|error| format!("Can't write replicate: {}", error))?;
+    let path = output.join("summary.csv");
+    fs::write(&path, Summary::new(&replicates).to_string())
+        .map_err(|error| format!("Can't write {}: {}", path.display(), error))
 }
 
-#[inline]
-fn do_report(pond: &CellPond, statistics: &mut Statistics) {
-    println!("{},{},{},{},{},{},{}",
-           statistics.clock,
-           pond.total_energy(),
-           pond.total_active_cells(),
-           pond.total_viable_replicators(),
-           pond.max_generation(),
-           statistics,
-           statistics.metabolism());
-    statistics.zero();
+fn sweep(args: &[String]) -> Result<(), String> {
+    let mut threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+    let mut output = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => threads = parse_value(&mut args, arg)?,
+            "--output" => output = Some(PathBuf::from(value(&mut args, arg)?)),
+            _ if path.is_none() => path = Some(arg.as_str()),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    let (path, output) = match (path, output) {
+        (Some(path), Some(output)) => (path, output),
+        _ => return Err(
+            "Usage: rustedpond sweep <experiment> --output <directory> [--threads <n>]".to_string()),
+    };
+    let sweep = fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path, error))
+        .and_then(|text| Sweep::parse(&text).map_err(|error| format!("Can't parse {}: {}", path, error)))?;
+    fs::create_dir_all(&output)
+        .map_err(|error| format!("Can't create {}: {}", output.display(), error))?;
+    let index = output.join("index.csv");
+    fs::write(&index, sweep.index().to_string())
+        .map_err(|error| format!("Can't write {}: {}", index.display(), error))?;
+    for condition in &sweep.conditions {
+        eprintln!("{}", condition.name);
+        run_batch(&sweep.batch(condition, threads)?, &output.join(&condition.name))?;
+    }
+    Ok(())
 }
 
-fn run(mut pond: CellPond,
-       mut id_generator: CellIdGenerator,
-       mut random_generator: RandomGenerator,
-       mut statistics: Statistics) {
-    loop {
-        statistics.clock += 1;
-        if statistics.clock % REPORT_FREQUENCY == 0 {
-            do_report(&pond, &mut statistics);
+fn bench(args: &[String]) -> Result<(), String> {
+    let mut benchmark = Benchmark {
+        config: Config::default(),
+        seed: 1,
+        ticks: 10_000_000,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ticks" => benchmark.ticks = parse_value(&mut args, arg)?,
+            "--seed" => benchmark.seed = parse_value(&mut args, arg)?,
+            "--set" => set_parameter(&mut benchmark.config, &value(&mut args, arg)?)?,
+            _ => return Err(format!(
+                "Unknown argument {}\nUsage: rustedpond bench [--ticks <ticks>] [--seed <seed>] [--set <key>=<value>]...",
+                arg)),
         }
-        if statistics.clock % INFLOW_FREQUENCY == 0 {
-            mutate_cell(&mut pond, &mut random_generator, &mut id_generator);
+    }
+    print!("{}", benchmark.run());
+    Ok(())
+}
+
+fn do_report<G: GenomeBuffer>(simulation: &mut Simulation<G>, id_generator: Option<&CellIdGenerator>, reporter: &mut Reporter) {
+    let sample = simulation.sample();
+    let parameters = simulation.parameters();
+    let Simulation { ref config, ref mut pond, ref mut statistics, .. } = *simulation;
+    print!("{},{},{}", sample, statistics, sample.metabolism);
+    if !config.schedules.is_empty() {
+        print!(",{}", parameters);
+    }
+    if reporter.options.classify {
+        print!(",{}", pond.class_census());
+    }
+    if let Some(ngrams) = statistics.ngrams() {
+        print!(",{}", ngrams);
+    }
+    if let Some(genomes) = pond.shared_genomes() {
+        print!(",{}", genomes);
+    }
+    println!();
+    if let (Some(file), Some(lineages)) = (reporter.lineages.as_mut(), statistics.lineages()) {
+        write_report(file, statistics.clock, lineages);
+    }
+    if let (Some(file), Some(positions)) = (reporter.positions.as_mut(), statistics.positions()) {
+        write_report(file, statistics.clock, positions);
+    }
+    if let Some(ref mut motifs) = reporter.motifs {
+        let report = MotifReport::new(pond, &reporter.options.motif_options);
+        write_report(motifs, statistics.clock, &report);
+    }
+    if let Some(log) = statistics.reproduction_log() {
+        log.flush().expect("Can't write reproduction log");
+    }
+    if let Some(ref path) = reporter.options.checkpoint {
+        let id_generator = id_generator.unwrap_or(&simulation.id_generator);
+        write_checkpoint(path, statistics.clock, pond.recorded_events(), id_generator, pond)
+            .expect("Can't write checkpoint");
+    }
+    pond.flush_events().expect("Can't write events");
+    statistics.zero();
+}
+
+#[inline]
+fn finished<G: GenomeBuffer>(simulation: &Simulation<G>, reporter: &Reporter) -> bool {
+    reporter.options.ticks > 0 && simulation.statistics.clock >= reporter.options.ticks
+}
+
+fn run<G: GenomeBuffer>(mut simulation: Simulation<G>, mut reporter: Reporter) {
+    while !finished(&simulation, &reporter) {
+        simulation.tick(|simulation| do_report(simulation, None, &mut reporter));
+    }
+}
+
+fn run_parallel<G: GenomeBuffer>(mut simulation: Simulation<G>, mut reporter: Reporter, mut executor: ParallelExecutor) {
+    while !finished(&simulation, &reporter) {
+        let start = simulation.statistics.clock;
+        executor.execute_round(&mut simulation.pond, &mut simulation.statistics);
+        // The round divides the report frequency, so a report can only be
+        // due at its last tick, after all of its executions.
+        for tick in start + 1..start + executor.round() + 1 {
+            simulation.statistics.clock = tick;
+            simulation.follow_schedules();
+            simulation.follow_disturbances();
+            if tick.is_multiple_of(simulation.config.report_frequency) {
+                let ids = CellIdGenerator::after(
+                    iter::once(&simulation.id_generator).chain(executor.id_generators()));
+                do_report(&mut simulation, Some(&ids), &mut reporter);
+            }
+            if tick.is_multiple_of(simulation.parameters().inflow_frequency) {
+                simulation.inflow();
+            }
         }
-        execute_cell(&mut pond, &mut random_generator, &mut id_generator, &mut statistics);
     }
 }
 
 fn main() {
-    let fseed = get_timestamp();
-    let sseed = get_timestamp();
-    let mut id_generator = CellIdGenerator::new();
-    let mut random_generator = RandomGenerator::new(fseed, sseed);
-    let statistics = Statistics::new();
-    let pond = CellPond::new(&mut id_generator, &mut random_generator);
-    run(pond, id_generator, random_generator, statistics);
-    println!("Hello, world!");
-}
\ No newline at end of file
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command: Option<Command> =
+        match args.first().map(|arg| arg.as_str()) {
+            Some("diff") => Some(diff),
+            Some("replay") => Some(replay),
+            Some("motifs") => Some(motifs),
+            Some("batch") => Some(batch),
+            Some("sweep") => Some(sweep),
+            Some("bench") => Some(bench),
+            _ => None,
+        };
+    if let Some(command) = command {
+        if let Err(error) = command(&args[1..]) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    let reporter = Options::parse(&args)
+        .and_then(Reporter::new)
+        .unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+    let random_generator = match reporter.options.seed {
+        Some(seed) => RandomGenerator::from_seed(reporter.options.config.rng, seed as u64),
+        None => RandomGenerator::from_seed(reporter.options.config.rng, get_timestamp() as u64),
+    };
+    let config = reporter.options.config.clone();
+    if reporter.options.share_genomes {
+        start(Simulation::sharing_genomes(config, random_generator), reporter);
+    } else {
+        start(Simulation::new(config, random_generator), reporter);
+    }
+}
+
+/// Sets up `simulation` as the options say and runs it.
+fn start<G: GenomeBuffer>(mut simulation: Simulation<G>, reporter: Reporter) {
+    apply_masks(&mut simulation, &reporter.options).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    match read_genome_bank(&reporter.options) {
+        Ok(Some(bank)) => simulation.set_genome_bank(bank),
+        Ok(None) => (),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        },
+    }
+    if let Some(top) = reporter.options.ngrams {
+        simulation.statistics.track_ngrams(top);
+    }
+    if reporter.options.position_check.is_some() {
+        simulation.statistics.track_positions(simulation.pond.width(), simulation.pond.height());
+    }
+    if reporter.options.lineages.is_some() {
+        simulation.statistics.track_lineages(reporter.options.lineage_key, reporter.options.lineage_top);
+    }
+    if let Some(ref path) = reporter.options.reproduction_log {
+        let log = File::create(path)
+            .and_then(|file| ReproductionLog::new(
+                Box::new(BufWriter::new(file)), reporter.options.reproduction_log_format))
+            .unwrap_or_else(|error| {
+                eprintln!("Can't create {}: {}", path, error);
+                process::exit(1);
+            });
+        simulation.statistics.log_reproductions(log);
+    }
+    if let Some(ref path) = reporter.options.checkpoint {
+        write_checkpoint(path, 0, 0, &simulation.id_generator, &simulation.pond).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+    }
+    if let Some(ref path) = reporter.options.events {
+        let log = File::create(path)
+            .and_then(|file| EventLog::new(Box::new(BufWriter::new(file))))
+            .unwrap_or_else(|error| {
+                eprintln!("Can't create {}: {}", path, error);
+                process::exit(1);
+            });
+        simulation.pond.record_events(log);
+    }
+    if let Some(ref path) = reporter.options.disturbances {
+        let disturbances = read_disturbances(path, &reporter.options.disturbance_log).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        });
+        simulation.set_disturbances(disturbances);
+    }
+    if let Some((columns, rows)) = reporter.options.tiles {
+        let executor = ParallelExecutor::new(columns,
+                                             rows,
+                                             reporter.options.round,
+                                             reporter.options.threads,
+                                             &mut simulation)
+            .unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                process::exit(1);
+            });
+        run_parallel(simulation, reporter, executor);
+    } else {
+        run(simulation, reporter);
+    }
+}